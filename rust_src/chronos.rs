@@ -1,15 +1,26 @@
 use std::{
-    cell::RefCell, collections::HashMap, collections::HashSet, fmt, fmt::Debug, mem, rc::Rc,
+    cell::{Cell, RefCell},
+    collections::hash_map::{DefaultHasher, RandomState},
+    collections::HashMap,
+    collections::HashSet,
+    fmt,
+    fmt::Debug,
+    hash::BuildHasher,
+    io::{self, Write},
+    mem,
+    path::PathBuf,
+    rc::Rc,
 };
 
 use crate::datatypes::*;
 use crate::errors::*;
 use crate::interpreter::*;
-use crate::lexer::Lexer;
+use crate::json::{from_json, to_json};
+use crate::lexer::{is_incomplete, Lexer};
+use crate::lint::check;
 use crate::parser::Parser;
 
 pub const DIGITS: &str = "0123456789";
-pub const LETTERS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
 
 pub type ChInt = i32;
 pub type ChFloat = f32;
@@ -19,16 +30,15 @@ pub fn match_enum_type<T>(t1: &T, t2: &T) -> bool {
     mem::discriminant(t1) == mem::discriminant(t2)
 }
 
-//TODO: remove file_name and text from position!!!
 #[derive(Debug, Clone, Copy, Default)]
 pub struct Position {
-    //pub file_name: Rc<String>,
+    //index into FileManager::files
     pub file_nr: usize,
     pub index: usize,
+    //start of the lexed chunk inside the file text
     pub offset: usize,
     pub line: usize,
     pub column: usize,
-    //pub text: Rc<String>,
 }
 
 impl Position {
@@ -39,7 +49,6 @@ impl Position {
             offset,
             line,
             column,
-            //text: Rc::new(text),
         }
     }
 
@@ -50,6 +59,7 @@ impl Position {
         }
     }
 
+    //index counts bytes of the utf-8 text, column counts chars
     pub fn advance(&mut self, current_char: &Option<char>) {
         match *current_char {
             Some('\n') => {
@@ -59,7 +69,7 @@ impl Position {
             }
 
             _ => {
-                self.index += 1;
+                self.index += current_char.map_or(1, char::len_utf8);
                 self.column += 1;
             }
         }
@@ -76,7 +86,17 @@ pub enum Keyword {
     Else,
     While,
     For,
+    In,
     Func,
+    Del,
+    Let,
+    Const,
+    Do,
+    Try,
+    Catch,
+    Throw,
+    Import,
+    Break,
 }
 
 pub fn get_keyword(s: &str) -> Result<Keyword, ()> {
@@ -89,7 +109,17 @@ pub fn get_keyword(s: &str) -> Result<Keyword, ()> {
         "else" => Ok(Keyword::Else),
         "while" => Ok(Keyword::While),
         "for" => Ok(Keyword::For),
+        "in" => Ok(Keyword::In),
         "fn" => Ok(Keyword::Func),
+        "del" => Ok(Keyword::Del),
+        "let" => Ok(Keyword::Let),
+        "const" => Ok(Keyword::Const),
+        "do" => Ok(Keyword::Do),
+        "try" => Ok(Keyword::Try),
+        "catch" => Ok(Keyword::Catch),
+        "throw" => Ok(Keyword::Throw),
+        "import" => Ok(Keyword::Import),
+        "break" => Ok(Keyword::Break),
         _ => Err(()),
     }
 }
@@ -101,10 +131,16 @@ pub enum TokenType {
     Float(ChFloat),
     Add,
     AddEq,
+    PlusPlus,
     Sub,
     SubEq,
+    MinusMinus,
     Mul,
+    MulEq,
     Div,
+    DivEq,
+    Mod,
+    ModEq,
     Pow,
     LRound,
     RRound,
@@ -113,7 +149,14 @@ pub enum TokenType {
     LBrace,
     RBrace,
     Semicln,
+    Newline,
+    //text between /* and */, only with Settings::keep_comments
+    Comment(String),
     Comma,
+    Question,
+    Coalesce,
+    Colon,
+    Dot,
     Eof,
 
     Id(String),
@@ -133,6 +176,9 @@ pub struct Token {
     pub token_type: TokenType,
     pub start_pos: Position,
     pub end_pos: Position,
+    //source text of number literals, only kept when it differs from the
+    //printed value and the lexer preserves literals
+    pub literal: Option<Rc<str>>,
 }
 
 impl fmt::Debug for Token {
@@ -148,6 +194,7 @@ impl Token {
                 token_type,
                 start_pos,
                 end_pos,
+                literal: None,
             };
         }
 
@@ -158,6 +205,7 @@ impl Token {
             token_type,
             start_pos,
             end_pos,
+            literal: None,
         }
     }
 }
@@ -167,23 +215,305 @@ pub enum Node {
     Num(Token),
     String(Token),
     Array(Vec<Node>, Position, Position),
+    Map(Vec<(Node, Node)>, Position, Position),
     BinOp(Box<Node>, Token, Box<Node>),
     UnryOp(Token, Box<Node>),
+    PrefixOp(Token, Box<Node>),
+    PostfixOp(Box<Node>, Token),
     Assign(Token, Box<Node>),
+    //'a, b = value', value has to be an array with one element per name
+    MultiAssign(Vec<Token>, Box<Node>),
+    Let(Token, Box<Node>),
+    Const(Token, Box<Node>),
     Access(Token),
     If(Vec<(Node, Node)>, Option<Box<Node>>),
-    While(Box<Node>, Box<Node>, Position, Position),
+    //loops take an optional 'else { ... }' that runs unless 'break' left the loop
+    While(Box<Node>, Box<Node>, Option<Box<Node>>, Position, Position),
+    //'do { body } while cond', the body runs before the first check
+    DoWhile(Box<Node>, Box<Node>, Option<Box<Node>>, Position, Position),
+    //'try { body } catch e { handler }'
+    Try(Box<Node>, Token, Box<Node>, Position, Position),
     For(
         Option<Box<Node>>,
-        Box<Node>,
+        Option<Box<Node>>,
         Option<Box<Node>>,
         Box<Node>,
+        Option<Box<Node>>,
         Position,
         Position,
     ),
-    FuncDef(Option<Token>, Vec<Token>, Box<Node>, Position, Position),
+    ForEach(Token, Box<Node>, Box<Node>, Option<Box<Node>>, Position, Position),
+    FuncDef(Option<Token>, Vec<(Token, Option<Node>)>, Box<Node>, Position, Position),
     Call(Box<Node>, Vec<Node>),
+    //'name = value' in the arguments of a call
+    NamedArg(Token, Box<Node>),
     ArrAccess(Box<Node>, Box<Node>),
+    //value[start:end], both bounds are optional
+    Slice(Box<Node>, Option<Box<Node>>, Option<Box<Node>>),
+    Member(Box<Node>, Token),
+    Ternary(Box<Node>, Box<Node>, Box<Node>),
+    Del(Token),
+    //'import "path"', the path is relative to the importing file
+    Import(Token),
+    //'throw value', a runtime error with the value as its message
+    Throw(Box<Node>, Position, Position),
+    //'break' leaves the innermost loop, the parser only allows it in loop bodies
+    Break(Token),
+    Block(Vec<Node>, Position, Position),
+}
+
+fn token_name(token: &Token) -> String {
+    match &token.token_type {
+        TokenType::Int(v) => v.to_string(),
+        TokenType::Float(v) => v.to_string(),
+        TokenType::String(s) => format!("{:?}", s),
+        TokenType::Id(s) => s.to_string(),
+        TokenType::Keywrd(k) => format!("{:?}", k).to_uppercase(),
+        t => format!("{:?}", t).to_uppercase(),
+    }
+}
+
+impl Node {
+    pub fn children(&self) -> Vec<&Node> {
+        match self {
+            Node::Num(_)
+            | Node::String(_)
+            | Node::Access(_)
+            | Node::Del(_)
+            | Node::Import(_)
+            | Node::Break(_) => Vec::new(),
+            Node::Array(nodes, ..) => nodes.iter().collect(),
+            Node::Map(entries, ..) => entries.iter().flat_map(|(key, value)| [key, value]).collect(),
+            Node::BinOp(left, _, right) => vec![left, right],
+            Node::UnryOp(_, node) | Node::PrefixOp(_, node) | Node::PostfixOp(node, _) => vec![node],
+            Node::Assign(_, value) | Node::Let(_, value) | Node::Const(_, value) => vec![value],
+            Node::MultiAssign(_, value) => vec![value],
+            Node::If(cases, else_case) => cases
+                .iter()
+                .flat_map(|(cond, expr)| [cond, expr])
+                .chain(else_case.as_deref())
+                .collect(),
+            Node::While(cond, body, else_case, ..) => {
+                [cond.as_ref(), body].into_iter().chain(else_case.as_deref()).collect()
+            }
+            Node::DoWhile(body, cond, else_case, ..) => {
+                [body.as_ref(), cond].into_iter().chain(else_case.as_deref()).collect()
+            }
+            Node::Try(body, _, handler, ..) => vec![body, handler],
+            Node::Throw(value, ..) => vec![value],
+            Node::For(c1, c2, c3, body, else_case, ..) => c1
+                .as_deref()
+                .into_iter()
+                .chain(c2.as_deref())
+                .chain(c3.as_deref())
+                .chain([body.as_ref()])
+                .chain(else_case.as_deref())
+                .collect(),
+            Node::ForEach(_, collection, body, else_case, ..) => {
+                [collection.as_ref(), body].into_iter().chain(else_case.as_deref()).collect()
+            }
+            Node::FuncDef(_, args, body, ..) => args
+                .iter()
+                .filter_map(|(_, default)| default.as_ref())
+                .chain([body.as_ref()])
+                .collect(),
+            Node::Call(func, args) => [func.as_ref()].into_iter().chain(args).collect(),
+            Node::NamedArg(_, value) => vec![value],
+            Node::ArrAccess(array, index) => vec![array, index],
+            Node::Slice(value, start, end) => [value.as_ref()]
+                .into_iter()
+                .chain(start.as_deref())
+                .chain(end.as_deref())
+                .collect(),
+            Node::Member(node, _) => vec![node],
+            Node::Ternary(cond, then_expr, else_expr) => vec![cond, then_expr, else_expr],
+            Node::Block(nodes, ..) => nodes.iter().collect(),
+        }
+    }
+
+    //source range from the first to the last token of the node
+    pub fn span(&self) -> (Position, Position) {
+        match self {
+            Node::Num(t)
+            | Node::String(t)
+            | Node::Access(t)
+            | Node::Del(t)
+            | Node::Import(t)
+            | Node::Break(t) => (t.start_pos, t.end_pos),
+            Node::Array(_, start, end)
+            | Node::Map(_, start, end)
+            | Node::While(_, _, _, start, end)
+            | Node::DoWhile(_, _, _, start, end)
+            | Node::Try(_, _, _, start, end)
+            | Node::Throw(_, start, end)
+            | Node::For(_, _, _, _, _, start, end)
+            | Node::ForEach(_, _, _, _, start, end)
+            | Node::FuncDef(_, _, _, start, end)
+            | Node::Block(_, start, end) => (*start, *end),
+            Node::BinOp(left, _, right) => (left.span().0, right.span().1),
+            Node::UnryOp(op, node) | Node::PrefixOp(op, node) => (op.start_pos, node.span().1),
+            Node::PostfixOp(node, op) => (node.span().0, op.end_pos),
+            Node::Assign(id, value) | Node::Let(id, value) | Node::Const(id, value) => {
+                (id.start_pos, value.span().1)
+            }
+            Node::MultiAssign(ids, value) => (ids[0].start_pos, value.span().1),
+            Node::If(cases, else_case) => {
+                let start = cases.first().map(|(cond, _)| cond.span().0).unwrap_or_default();
+                let end = match (else_case, cases.last()) {
+                    (Some(node), _) => node.span().1,
+                    (None, Some((_, expr))) => expr.span().1,
+                    (None, None) => start,
+                };
+                (start, end)
+            }
+            Node::Call(func, args) => (func.span().0, args.last().unwrap_or(func).span().1),
+            Node::NamedArg(id, value) => (id.start_pos, value.span().1),
+            Node::ArrAccess(array, index) => (array.span().0, index.span().1),
+            Node::Slice(value, start, end) => {
+                let last = end.as_deref().or(start.as_deref()).unwrap_or(value);
+                (value.span().0, last.span().1)
+            }
+            Node::Member(node, member) => (node.span().0, member.end_pos),
+            Node::Ternary(cond, _, else_expr) => (cond.span().0, else_expr.span().1),
+        }
+    }
+
+    //s-expression view of the AST, e.g. (BINOP (NUM 1) ADD (NUM 2)), bodies
+    //of ifs, loops and functions go on their own lines
+    pub fn pretty(&self, indent: usize) -> String {
+        let pad = " ".repeat(indent + 2);
+        let body = |n: &Node| format!("\n{}{}", pad, n.pretty(indent + 2));
+        let opt = |n: &Option<Box<Node>>| match n {
+            Some(n) => n.pretty(indent),
+            None => String::from("_"),
+        };
+        let else_body = |n: &Option<Box<Node>>| match n {
+            Some(n) => format!("\n{}(ELSE\n{}{})", pad, " ".repeat(indent + 4), n.pretty(indent + 4)),
+            None => String::new(),
+        };
+        let list = |nodes: &[Node]| {
+            nodes
+                .iter()
+                .map(|n| format!(" {}", n.pretty(indent)))
+                .collect::<String>()
+        };
+
+        match self {
+            Node::Num(t) => format!("(NUM {})", token_name(t)),
+            Node::String(t) => format!("(STRING {})", token_name(t)),
+            Node::Array(nodes, ..) => format!("(ARRAY{})", list(nodes)),
+            Node::Map(entries, ..) => format!(
+                "(MAP{})",
+                entries
+                    .iter()
+                    .map(|(k, v)| format!(" ({} {})", k.pretty(indent), v.pretty(indent)))
+                    .collect::<String>()
+            ),
+            Node::BinOp(l, op, r) => format!(
+                "(BINOP {} {} {})",
+                l.pretty(indent),
+                token_name(op),
+                r.pretty(indent)
+            ),
+            Node::UnryOp(op, n) => format!("(UNRYOP {} {})", token_name(op), n.pretty(indent)),
+            Node::PrefixOp(op, n) => format!("(PREFIX {} {})", token_name(op), n.pretty(indent)),
+            Node::PostfixOp(n, op) => format!("(POSTFIX {} {})", n.pretty(indent), token_name(op)),
+            Node::Assign(t, n) => format!("(ASSIGN {} {})", token_name(t), n.pretty(indent)),
+            Node::MultiAssign(ids, n) => format!(
+                "(ASSIGN ({}) {})",
+                ids.iter().map(token_name).collect::<Vec<_>>().join(" "),
+                n.pretty(indent)
+            ),
+            Node::Let(t, n) => format!("(LET {} {})", token_name(t), n.pretty(indent)),
+            Node::Const(t, n) => format!("(CONST {} {})", token_name(t), n.pretty(indent)),
+            Node::Access(t) => format!("(ACCESS {})", token_name(t)),
+            Node::If(cases, else_case) => {
+                let inner = " ".repeat(indent + 4);
+                let mut s = String::from("(IF");
+                for (cond, expr) in cases {
+                    s += &format!(
+                        "\n{}(CASE {}\n{}{})",
+                        pad,
+                        cond.pretty(indent + 2),
+                        inner,
+                        expr.pretty(indent + 4)
+                    );
+                }
+                if let Some(e) = else_case {
+                    s += &format!("\n{}(ELSE\n{}{})", pad, inner, e.pretty(indent + 4));
+                }
+                s + ")"
+            }
+            Node::While(cond, b, e, ..) => format!(
+                "(WHILE {}{}{})",
+                cond.pretty(indent),
+                body(b),
+                else_body(e)
+            ),
+            Node::DoWhile(b, cond, e, ..) => format!(
+                "(DO-WHILE {}{}{})",
+                cond.pretty(indent),
+                body(b),
+                else_body(e)
+            ),
+            Node::Try(b, id, handler, ..) => format!(
+                "(TRY{}\n{}(CATCH {}\n{}{}))",
+                body(b),
+                pad,
+                token_name(id),
+                " ".repeat(indent + 4),
+                handler.pretty(indent + 4)
+            ),
+            Node::For(c1, c2, c3, b, e, ..) => format!(
+                "(FOR {} {} {}{}{})",
+                opt(c1),
+                opt(c2),
+                opt(c3),
+                body(b),
+                else_body(e)
+            ),
+            Node::ForEach(var, coll, b, e, ..) => format!(
+                "(FOREACH {} {}{}{})",
+                token_name(var),
+                coll.pretty(indent),
+                body(b),
+                else_body(e)
+            ),
+            Node::FuncDef(name, args, b, ..) => format!(
+                "(FUNCDEF {} ({}){})",
+                name.as_ref().map_or(String::from("_"), token_name),
+                args.iter()
+                    .map(|(arg, default)| match default {
+                        Some(d) => format!("({} {})", token_name(arg), d.pretty(indent)),
+                        None => token_name(arg),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                body(b)
+            ),
+            Node::Call(f, args) => format!("(CALL {}{})", f.pretty(indent), list(args)),
+            Node::NamedArg(id, value) => format!("({} {})", token_name(id), value.pretty(indent)),
+            Node::ArrAccess(arr, i) => format!("(INDEX {} {})", arr.pretty(indent), i.pretty(indent)),
+            Node::Slice(value, start, end) => format!(
+                "(SLICE {} {} {})",
+                value.pretty(indent),
+                opt(start),
+                opt(end)
+            ),
+            Node::Member(node, member) => format!("(MEMBER {} {})", node.pretty(indent), token_name(member)),
+            Node::Ternary(c, a, b) => format!(
+                "(TERNARY {} {} {})",
+                c.pretty(indent),
+                a.pretty(indent),
+                b.pretty(indent)
+            ),
+            Node::Del(t) => format!("(DEL {})", token_name(t)),
+            Node::Import(t) => format!("(IMPORT {})", token_name(t)),
+            Node::Throw(value, ..) => format!("(THROW {})", value.pretty(indent)),
+            Node::Break(_) => String::from("(BREAK)"),
+            Node::Block(nodes, ..) => format!("(BLOCK{})", nodes.iter().map(body).collect::<String>()),
+        }
+    }
 }
 
 impl ConvertValue for bool {
@@ -194,10 +524,6 @@ impl ConvertValue for bool {
     fn get_number_type(&self) -> NumberType {
         NumberType::Int(if *self { 1 } else { 0 })
     }
-
-    fn convert_to_number(self) -> Result<NumberType, Error> {
-        Ok(NumberType::Int(if self { 1 } else { 0 }))
-    }
 }
 
 impl ConvertValue for ChInt {
@@ -208,10 +534,6 @@ impl ConvertValue for ChInt {
     fn get_number_type(&self) -> NumberType {
         NumberType::Int(*self)
     }
-
-    fn convert_to_number(self) -> Result<NumberType, Error> {
-        Ok(NumberType::Int(self))
-    }
 }
 
 impl ConvertValue for ChFloat {
@@ -222,9 +544,59 @@ impl ConvertValue for ChFloat {
     fn get_number_type(&self) -> NumberType {
         NumberType::Float(*self)
     }
+}
+
+//interpreter options set on the Compiler, shared by all scopes
+#[derive(Debug, Clone)]
+pub struct Settings {
+    //turn panics in rust functions into runtime errors
+    pub catch_panics: bool,
+    //numbers that come from a literal print as written, e.g. 1.50
+    pub preserve_literals: bool,
+    //tokenize returns comments as tokens (e.g. for a formatter), the parser
+    //skips them
+    pub keep_comments: bool,
+    //assigning to a variable that was not declared with let is an error
+    pub strict: bool,
+    //nested function calls before a runtime error, instead of a stack overflow
+    pub max_call_depth: usize,
+    //function calls that are running right now, also the ones made by rust
+    //functions like map
+    pub call_depth: Rc<Cell<usize>>,
+    //visited nodes per interpret or call_function before a runtime error
+    pub step_limit: Option<u64>,
+    //steps taken so far, shared by every scope of the compiler
+    pub steps: Rc<Cell<u64>>,
+    //where print writes to
+    pub output: Output,
+    //the text of everything that was lexed, errors print their preview from
+    //it. Shared by every scope, imports add files while interpreting
+    pub files: Rc<RefCell<FileManager>>,
+}
 
-    fn convert_to_number(self) -> Result<NumberType, Error> {
-        Ok(NumberType::Float(self))
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            catch_panics: false,
+            preserve_literals: false,
+            keep_comments: false,
+            strict: false,
+            max_call_depth: 100,
+            call_depth: Rc::default(),
+            step_limit: None,
+            steps: Rc::default(),
+            output: Output(Rc::new(RefCell::new(io::stdout()))),
+            files: Rc::default(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Output(pub Rc<RefCell<dyn Write>>);
+
+impl Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Output")
     }
 }
 
@@ -234,6 +606,10 @@ pub struct Scope {
     pub parent: Option<Rc<RefCell<Scope>>>,
     pub position: Option<Position>,
     pub symbol_table: SymbolTable,
+    pub settings: Rc<Settings>,
+    //for function scopes the scope of the call site, tracebacks follow it
+    //instead of the parent, which is the scope the function was defined in
+    pub caller: Option<Rc<RefCell<Scope>>>,
 }
 
 impl Scope {
@@ -243,6 +619,8 @@ impl Scope {
             parent: None,
             position: None,
             symbol_table: SymbolTable::default(),
+            settings: Rc::default(),
+            caller: None,
         }
     }
 
@@ -251,11 +629,16 @@ impl Scope {
         parent: Rc<RefCell<Scope>>,
         position: Option<Position>,
     ) -> Rc<RefCell<Self>> {
+        let symbol_table = parent.borrow().symbol_table.empty_like();
+        let settings = parent.borrow().settings.clone();
+
         Rc::new(RefCell::new(Scope {
             display_name,
             parent: Some(parent),
             position,
-            symbol_table: SymbolTable::default(),
+            symbol_table,
+            settings,
+            caller: None,
         }))
     }
 
@@ -286,6 +669,28 @@ impl Scope {
         }
     }
 
+    //the name visible from this scope that is closest to key, for typos like
+    //'pirnt'. Short names need a closer match, so 'x' never suggests 'y'
+    pub fn closest_name(&self, key: &str) -> Option<String> {
+        let len = key.chars().count();
+        let max_distance = (len / 3 + 1).min(len.saturating_sub(1));
+
+        let mut names = self.symbol_table.names();
+        let mut parent = self.parent.clone();
+        while let Some(p) = parent {
+            names.extend(p.borrow().symbol_table.names());
+            parent = p.borrow().parent.clone();
+        }
+
+        names
+            .into_iter()
+            .filter(|name| name != key)
+            .map(|name| (edit_distance(key, &name), name))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min()
+            .map(|(_, name)| name)
+    }
+
     pub fn set_mut(&mut self, key: &str, value: ChValue) -> bool {
         self.symbol_table.set_mut(key, value)
     }
@@ -293,20 +698,186 @@ impl Scope {
     pub fn set(&mut self, key: &str, value: ChValue) -> bool {
         self.symbol_table.set(key, value)
     }
+
+    //updates key in the nearest scope that defines it, None if key is not
+    //defined and Some(false) if it is immutable
+    pub fn assign(&mut self, key: &str, value: ChValue) -> Option<bool> {
+        if self.symbol_table.contains(key) {
+            Some(self.symbol_table.set_mut(key, value))
+        } else {
+            match &self.parent {
+                Some(p) => p.borrow_mut().assign(key, value),
+                None => None,
+            }
+        }
+    }
+
+    //what assign would return, without changing anything
+    pub fn can_assign(&self, key: &str) -> Option<bool> {
+        if self.symbol_table.contains(key) {
+            Some(!self.symbol_table.is_immutable(key))
+        } else {
+            self.parent.as_ref().and_then(|p| p.borrow().can_assign(key))
+        }
+    }
+
+    //removes key from the nearest scope that defines it, None if key is not
+    //defined and Some(false) if it is immutable
+    pub fn remove(&mut self, key: &str) -> Option<bool> {
+        if self.symbol_table.contains(key) {
+            Some(self.symbol_table.remove(key))
+        } else {
+            match &self.parent {
+                Some(p) => p.borrow_mut().remove(key),
+                None => None,
+            }
+        }
+    }
+}
+
+//Fixed hashes with constant keys, which makes the iteration order of a
+//SymbolTable reproducible across runs (e.g. for snapshot tests)
+#[derive(Debug, Clone)]
+pub enum SymbolHasher {
+    Random(RandomState),
+    Fixed,
+}
+
+impl Default for SymbolHasher {
+    fn default() -> Self {
+        SymbolHasher::Random(RandomState::new())
+    }
+}
+
+impl BuildHasher for SymbolHasher {
+    type Hasher = DefaultHasher;
+
+    fn build_hasher(&self) -> DefaultHasher {
+        match self {
+            SymbolHasher::Random(state) => state.build_hasher(),
+            SymbolHasher::Fixed => DefaultHasher::new(),
+        }
+    }
+}
+
+//levenshtein distance over chars
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
 }
 
 #[derive(Debug, Clone, Default)]
 pub struct SymbolTable {
-    table: HashMap<String, ChValue>,
+    table: HashMap<String, ChValue, SymbolHasher>,
     immutable: HashSet<String>,
 }
 
 impl SymbolTable {
-    fn get(&self, key: &str) -> Option<ChValue> {
+    pub fn deterministic() -> Self {
+        SymbolTable {
+            table: HashMap::with_hasher(SymbolHasher::Fixed),
+            immutable: HashSet::new(),
+        }
+    }
+
+    pub fn is_deterministic(&self) -> bool {
+        matches!(self.table.hasher(), SymbolHasher::Fixed)
+    }
+
+    fn empty_like(&self) -> Self {
+        if self.is_deterministic() {
+            SymbolTable::deterministic()
+        } else {
+            SymbolTable::default()
+        }
+    }
+
+    //entries are reinserted in sorted order, so the resulting layout only
+    //depends on the hasher and not on the previous iteration order
+    fn rehash(&mut self, hasher: SymbolHasher) {
+        let mut entries: Vec<(String, ChValue)> = self.table.drain().collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+        self.table = HashMap::with_hasher(hasher);
+        self.table.extend(entries);
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.table.keys().cloned().collect()
+    }
+
+    //true, false, none and the built-in functions
+    pub fn builtins() -> Self {
+        let mut table = SymbolTable::default();
+
+        table.set("false", ChValue::Bool(ChBool::from(false)));
+        table.set("true", ChValue::Bool(ChBool::from(true)));
+        table.set(
+            "none",
+            ChValue::None(ChNone {
+                start_pos: None,
+                end_pos: None,
+            }),
+        );
+
+        table.set_rust_func("print", "print[args...]", ch_print);
+        table.set_rust_func("len", "len[arg]", ch_len);
+        table.set_rust_func("exit", "exit[code]", ch_exit);
+        table.set_rust_func("type", "type[arg]", ch_type);
+        table.set_rust_func("bool", "bool[arg]", ch_bool);
+        table.set_rust_func("assert", "assert[cond, message]", ch_assert);
+        table.set_rust_func("format", "format[template, args...]", ch_format);
+        table.set_rust_func("to_json", "to_json[value]", ch_to_json);
+        table.set_rust_func("from_json", "from_json[string]", ch_from_json);
+        table.set_rust_func("input", "input[prompt]", ch_input);
+        table.set_rust_func("range", "range[start, end, step]", ch_range);
+        table.set_rust_func("push", "push[array, value]", ch_push);
+        table.set_rust_func("pop", "pop[array]", ch_pop);
+        table.set_rust_func("insert", "insert[array, index, value]", ch_insert);
+        table.set_rust_func("remove", "remove[array, index]", ch_remove);
+        table.set_rust_func("map", "map[array, function]", ch_map);
+        table.set_rust_func("filter", "filter[array, function]", ch_filter);
+        table.set_rust_func("reduce", "reduce[array, function, init]", ch_reduce);
+        table.set_rust_func("sort", "sort[array, compare]", ch_sort);
+        table.set_rust_func("reverse", "reverse[value]", ch_reverse);
+        table.set_rust_func("contains", "contains[string, pattern]", ch_contains);
+        table.set_rust_func("starts_with", "starts_with[string, prefix]", ch_starts_with);
+        table.set_rust_func("ends_with", "ends_with[string, suffix]", ch_ends_with);
+        table.set_rust_func("abs", "abs[number]", ch_abs);
+        table.set_rust_func("min", "min[a, b, args...]", ch_min);
+        table.set_rust_func("max", "max[a, b, args...]", ch_max);
+        table.set_rust_func("sqrt", "sqrt[number]", ch_sqrt);
+        table.set_rust_func("floor", "floor[number]", ch_floor);
+        table.set_rust_func("ceil", "ceil[number]", ch_ceil);
+        table.set_rust_func("round", "round[number]", ch_round);
+        table.set_rust_func("join", "join[array, separator]", ch_join);
+        table.set_rust_func("keys", "keys[map]", ch_keys);
+        table.set_rust_func("values", "values[map]", ch_values);
+        table.set_rust_func("enumerate", "enumerate[array]", ch_enumerate);
+        table.set_rust_func("popcount", "popcount[int]", ch_popcount);
+        table.set_rust_func("leading_zeros", "leading_zeros[int]", ch_leading_zeros);
+        table.set_rust_func("gcd", "gcd[a, b]", ch_gcd);
+
+        table
+    }
+
+    pub fn get(&self, key: &str) -> Option<ChValue> {
         self.table.get(key).cloned()
     }
 
-    fn set_mut(&mut self, key: &str, value: ChValue) -> bool {
+    pub fn set_mut(&mut self, key: &str, value: ChValue) -> bool {
         if self.table.contains_key(key) {
             if self.immutable.contains(key) {
                 false
@@ -320,7 +891,7 @@ impl SymbolTable {
         }
     }
 
-    fn set(&mut self, key: &str, value: ChValue) -> bool {
+    pub fn set(&mut self, key: &str, value: ChValue) -> bool {
         let b = self.set_mut(key, value);
         if b {
             self.immutable.insert(key.to_string());
@@ -328,33 +899,160 @@ impl SymbolTable {
         b
     }
 
-    fn remove(&mut self, key: &str) {
-        self.table.remove(key);
+    pub fn contains(&self, key: &str) -> bool {
+        self.table.contains_key(key)
+    }
+
+    pub fn is_immutable(&self, key: &str) -> bool {
+        self.immutable.contains(key)
+    }
+
+    pub fn remove(&mut self, key: &str) -> bool {
+        if self.immutable.contains(key) {
+            false
+        } else {
+            self.table.remove(key);
+            true
+        }
+    }
+
+    pub fn set_rust_func(
+        &mut self,
+        name: &str,
+        signature: &str,
+        function: RustFn,
+    ) {
+        self.set(
+            name,
+            ChValue::Function(ChFunction {
+                func_type: FuncType::RustFunc(RustFunc::new(signature, function)),
+            }),
+        );
     }
 }
 
-fn ch_print(args: Vec<ChValue>, _name: Option<String>) -> Result<ChValue, Error> {
-    let ret = ChValue::None(ChNone {
-        start_pos: None,
-        end_pos: None,
-    });
+fn expect_arg_count(args: &[ChValue], count: usize) -> Result<(), Error> {
+    if args.len() != count {
+        return Err(Error::runtime_at(
+            format!(
+                "Expected {} argument{} found: {}",
+                count,
+                if count == 1 { "" } else { "s" },
+                args.len()
+            ),
+            args.first().and_then(|a| a.get_start()),
+            args.last().and_then(|a| a.get_end()),
+        ));
+    }
+    Ok(())
+}
+
+fn expect_string(arg: &ChValue) -> Result<&str, Error> {
+    match arg {
+        ChValue::String(s) => Ok(&s.string),
+        _ => Err(Error::runtime_at(
+            format!("expected String found: {}", arg.type_name()),
+            arg.get_start(),
+            arg.get_end(),
+        )),
+    }
+}
+
+fn expect_map(arg: &ChValue) -> Result<&ChMap, Error> {
+    match arg {
+        ChValue::Map(m) => Ok(m),
+        _ => Err(Error::runtime_at(
+            format!("expected Map found: {}", arg.type_name()),
+            arg.get_start(),
+            arg.get_end(),
+        )),
+    }
+}
+
+fn expect_int(arg: &ChValue) -> Result<ChInt, Error> {
+    match arg {
+        ChValue::Number(ChNumber {
+            value: NumberType::Int(v),
+            ..
+        }) => Ok(*v),
+        _ => Err(Error::runtime_at(
+            format!("expected Int found: {}", arg),
+            arg.get_start(),
+            arg.get_end(),
+        )),
+    }
+}
+
+fn expect_array(arg: &ChValue) -> Result<&ChArray, Error> {
+    match arg {
+        ChValue::Array(a) => Ok(a),
+        _ => Err(Error::runtime_at(
+            format!("expected Array found: {}", arg.type_name()),
+            arg.get_start(),
+            arg.get_end(),
+        )),
+    }
+}
+
+fn expect_function(arg: &ChValue) -> Result<ChFunction, Error> {
+    match arg {
+        ChValue::Function(f) => Ok(f.clone()),
+        _ => Err(Error::runtime_at(
+            format!("expected Function found: {}", arg.type_name()),
+            arg.get_start(),
+            arg.get_end(),
+        )),
+    }
+}
+
+//index into an array of length len, insert may also use len itself
+fn expect_index(arg: &ChValue, len: usize) -> Result<usize, Error> {
+    let index = expect_int(arg)?;
+    if index < 0 || index as usize > len {
+        return Err(Error::runtime_at(
+            format!("Array index out of bounds => len: {}, index: {}", len, index),
+            arg.get_start(),
+            arg.get_end(),
+        ));
+    }
+    Ok(index as usize)
+}
+
+fn output_error(e: io::Error) -> Error {
+    Error::runtime(format!("could not write output: {}", e))
+}
+
+//returns its argument so it can be used inside expressions, several
+//arguments are returned as an Array
+fn ch_print(mut args: Vec<ChValue>, _name: Option<String>, settings: &Settings) -> Result<ChValue, Error> {
     if args.is_empty() {
-        return Ok(ret);
+        return Ok(ChValue::None(ChNone {
+            start_pos: None,
+            end_pos: None,
+        }));
     }
 
+    let mut output = settings.output.0.borrow_mut();
     let mut it = args.iter();
     let first = it.next();
-    print!("{}", first.unwrap());
+    write!(output, "{}", first.unwrap()).map_err(output_error)?;
 
     for arg in it {
-        print!(", {}", arg);
+        write!(output, ", {}", arg).map_err(output_error)?;
     }
-    println!();
+    writeln!(output).map_err(output_error)?;
+    drop(output);
 
-    Ok(ret)
+    if args.len() == 1 {
+        return Ok(args.pop().unwrap());
+    }
+
+    let start = args.first().and_then(|a| a.get_start());
+    let end = args.last().and_then(|a| a.get_end());
+    Ok(ChValue::Array(ChArray::new(args, start, end)))
 }
 
-fn ch_len(args: Vec<ChValue>, _name: Option<String>) -> Result<ChValue, Error> {
+fn ch_len(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
     let mut start: Option<Position> = None;
     let mut end: Option<Position> = None;
 
@@ -372,18 +1070,737 @@ fn ch_len(args: Vec<ChValue>, _name: Option<String>) -> Result<ChValue, Error> {
     start = arg.get_start();
     end = arg.get_end();
 
-    match arg {
-        ChValue::String(s) => Ok(ChValue::Number(ChNumber {
-            value: (s.string.len() as i32).get_number_type(),
-            start_pos: start,
-            end_pos: end,
-        })),
-        _ => Err(Error::new(
+    let len = match arg {
+        ChValue::String(s) => s.string.chars().count(),
+        ChValue::Array(a) => a.data.borrow().len(),
+        ChValue::Map(m) => m.data.borrow().len(),
+        _ => {
+            return Err(Error::new(
+                ErrType::Runtime,
+                start,
+                end,
+                format!("{} has no len", arg),
+                None,
+            ))
+        }
+    };
+
+    Ok(ChValue::Number(ChNumber {
+        value: (len as i32).get_number_type(),
+        start_pos: start,
+        end_pos: end,
+        literal: None,
+    }))
+}
+
+fn ch_exit(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    if args.len() > 1 {
+        return Err(Error::new(
             ErrType::Runtime,
-            start,
-            end,
-            format!("{} has no len", arg),
             None,
+            None,
+            format!("Expected at most 1 argument found: {}", args.len()),
+            None,
+        ));
+    }
+
+    let (code, start, end) = match args.first() {
+        Some(ChValue::Number(ChNumber {
+            value: NumberType::Int(v),
+            start_pos,
+            end_pos,
+            ..
+        })) => (*v, *start_pos, *end_pos),
+        Some(arg) => {
+            return Err(Error::new(
+                ErrType::Runtime,
+                arg.get_start(),
+                arg.get_end(),
+                format!("exit code must be an Int, found: {}", arg),
+                None,
+            ))
+        }
+        None => (0, None, None),
+    };
+
+    Err(Error::new(
+        ErrType::Signal(Signal::Exit(code)),
+        start,
+        end,
+        format!("exit with code {}", code),
+        None,
+    ))
+}
+
+fn ch_type(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 1)?;
+    let arg = &args[0];
+
+    Ok(ChValue::String(ChString {
+        string: arg.type_name(),
+        start_pos: arg.get_start(),
+        end_pos: arg.get_end(),
+    }))
+}
+
+//truthiness as used by if and while, see the table in datatypes.rs
+fn ch_bool(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 1)?;
+    let arg = &args[0];
+
+    Ok(ChValue::Bool(ChBool {
+        value: arg.convert_to_bool(),
+        start_pos: arg.get_start(),
+        end_pos: arg.get_end(),
+    }))
+}
+
+//assert(cond) or assert(cond, message)
+fn ch_assert(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(Error::runtime(format!(
+            "Expected 1 or 2 arguments found: {}",
+            args.len()
+        )));
+    }
+
+    let message = match args.get(1) {
+        Some(message) => expect_string(message)?,
+        None => "assertion failed",
+    };
+
+    let cond = &args[0];
+    if !cond.is_true() {
+        return Err(Error::runtime_at(message, cond.get_start(), cond.get_end()));
+    }
+
+    Ok(ChValue::None(ChNone {
+        start_pos: None,
+        end_pos: None,
+    }))
+}
+
+//every {} is replaced by the next argument, {{ and }} are literal braces
+fn ch_format(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    let template = match args.first() {
+        Some(template) => expect_string(template)?,
+        None => return Err(Error::runtime("Expected at least 1 argument found: 0")),
+    };
+
+    let error = |details: String| Error::runtime_at(details, args[0].get_start(), args[0].get_end());
+
+    let mut values = args[1..].iter();
+    let mut placeholders = 0;
+    let mut string = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                string.push(c);
+            }
+            ('{', Some('}')) => {
+                chars.next();
+                placeholders += 1;
+                if let Some(value) = values.next() {
+                    string.push_str(&value.to_string());
+                }
+            }
+            ('{', _) => return Err(error("format: expected '}' after '{', use '{{' for a literal brace".to_string())),
+            ('}', _) => return Err(error("format: unmatched '}', use '}}' for a literal brace".to_string())),
+            _ => string.push(c),
+        }
+    }
+
+    if placeholders != args.len() - 1 {
+        return Err(error(format!(
+            "format: the template has {} placeholder{} but {} argument{} given",
+            placeholders,
+            if placeholders == 1 { "" } else { "s" },
+            args.len() - 1,
+            if args.len() == 2 { " was" } else { "s were" }
+        )));
+    }
+
+    Ok(ChValue::String(ChString {
+        string,
+        start_pos: args[0].get_start(),
+        end_pos: args.last().and_then(|a| a.get_end()),
+    }))
+}
+
+fn ch_to_json(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 1)?;
+    let arg = &args[0];
+
+    Ok(ChValue::String(ChString {
+        string: to_json(arg)?,
+        start_pos: arg.get_start(),
+        end_pos: arg.get_end(),
+    }))
+}
+
+fn ch_from_json(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 1)?;
+    from_json(expect_string(&args[0])?)
+}
+
+fn ch_input(args: Vec<ChValue>, _name: Option<String>, settings: &Settings) -> Result<ChValue, Error> {
+    if args.len() > 1 {
+        return Err(Error::runtime(format!(
+            "Expected at most 1 argument found: {}",
+            args.len()
+        )));
+    }
+
+    if let Some(prompt) = args.first() {
+        let mut output = settings.output.0.borrow_mut();
+        write!(output, "{}", expect_string(prompt)?).map_err(output_error)?;
+        let _ = output.flush();
+    }
+
+    let mut line = String::new();
+    let read = io::stdin()
+        .read_line(&mut line)
+        .map_err(|e| Error::runtime(format!("could not read input: {}", e)))?;
+
+    //EOF
+    if read == 0 {
+        return Ok(ChValue::None(ChNone {
+            start_pos: None,
+            end_pos: None,
+        }));
+    }
+
+    let len = line.trim_end_matches(['\n', '\r']).len();
+    line.truncate(len);
+
+    Ok(ChValue::String(ChString {
+        string: line,
+        start_pos: None,
+        end_pos: None,
+    }))
+}
+
+//range(end), range(start, end) or range(start, end, step)
+fn ch_range(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    if args.is_empty() || args.len() > 3 {
+        return Err(Error::runtime(format!(
+            "Expected 1 to 3 arguments found: {}",
+            args.len()
+        )));
+    }
+
+    let ints = args.iter().map(expect_int).collect::<Result<Vec<_>, _>>()?;
+    let (start, end, step) = match ints[..] {
+        [end] => (0, end, 1),
+        [start, end] => (start, end, 1),
+        [start, end, step] => (start, end, step),
+        _ => unreachable!(),
+    };
+
+    if step == 0 {
+        return Err(Error::runtime_at(
+            "range step must not be zero",
+            args[2].get_start(),
+            args[2].get_end(),
+        ));
+    }
+
+    let mut data = Vec::new();
+    let mut i = start;
+    while (step > 0 && i < end) || (step < 0 && i > end) {
+        data.push(ChValue::Number(ChNumber {
+            value: NumberType::Int(i),
+            start_pos: None,
+            end_pos: None,
+            literal: None,
+        }));
+
+        i = match i.checked_add(step) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+
+    Ok(ChValue::Array(ChArray::new(
+        data,
+        args.first().and_then(|a| a.get_start()),
+        args.last().and_then(|a| a.get_end()),
+    )))
+}
+
+//the array builtins change the array in place, see ChArray
+fn ch_push(mut args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 2)?;
+    let value = args.pop().unwrap();
+    expect_array(&args[0])?.data.borrow_mut().push(value);
+
+    Ok(ChValue::None(ChNone {
+        start_pos: None,
+        end_pos: None,
+    }))
+}
+
+fn ch_pop(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 1)?;
+    let array = expect_array(&args[0])?;
+    let value = array.data.borrow_mut().pop();
+
+    value.ok_or_else(|| {
+        Error::runtime_at("pop from empty Array", array.start_pos, array.end_pos)
+    })
+}
+
+fn ch_insert(mut args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 3)?;
+    let value = args.pop().unwrap();
+    let array = expect_array(&args[0])?;
+    let index = expect_index(&args[1], array.len())?;
+    array.data.borrow_mut().insert(index, value);
+
+    Ok(ChValue::None(ChNone {
+        start_pos: None,
+        end_pos: None,
+    }))
+}
+
+fn ch_remove(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 2)?;
+    let array = expect_array(&args[0])?;
+    let len = array.len();
+    let index = expect_index(&args[1], len)?;
+
+    if index == len {
+        return Err(Error::runtime_at(
+            format!("Array index out of bounds => len: {}, index: {}", len, index),
+            args[1].get_start(),
+            args[1].get_end(),
+        ));
+    }
+
+    let value = array.data.borrow_mut().remove(index);
+    Ok(value)
+}
+
+//map, filter and reduce work on a snapshot of the array, the callback may
+//change the array itself
+fn ch_map(args: Vec<ChValue>, _name: Option<String>, settings: &Settings) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 2)?;
+    let array = expect_array(&args[0])?;
+    let mut func = expect_function(&args[1])?;
+    func.set_settings(settings);
+
+    let data = array
+        .clone()
+        .into_iter()
+        .map(|item| func.execute(vec![item], None))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ChValue::Array(ChArray::new(data, array.start_pos, array.end_pos)))
+}
+
+fn ch_filter(args: Vec<ChValue>, _name: Option<String>, settings: &Settings) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 2)?;
+    let array = expect_array(&args[0])?;
+    let mut func = expect_function(&args[1])?;
+    func.set_settings(settings);
+
+    let mut data = Vec::new();
+    for item in array.clone() {
+        if func.execute(vec![item.clone()], None)?.is_true() {
+            data.push(item);
+        }
+    }
+
+    Ok(ChValue::Array(ChArray::new(data, array.start_pos, array.end_pos)))
+}
+
+fn ch_reduce(mut args: Vec<ChValue>, _name: Option<String>, settings: &Settings) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 3)?;
+    let init = args.pop().unwrap();
+    let array = expect_array(&args[0])?;
+    let mut func = expect_function(&args[1])?;
+    func.set_settings(settings);
+
+    array
+        .clone()
+        .into_iter()
+        .try_fold(init, |acc, item| func.execute(vec![acc, item], None))
+}
+
+//a new sorted array. Numbers sort numerically and strings by their chars,
+//compare(a, b) returns a negative Number or true if a goes before b
+fn ch_sort(args: Vec<ChValue>, _name: Option<String>, settings: &Settings) -> Result<ChValue, Error> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(Error::runtime(format!(
+            "Expected 1 or 2 arguments found: {}",
+            args.len()
+        )));
+    }
+
+    let array = expect_array(&args[0])?;
+    let items = array.data.borrow().clone();
+
+    let sorted = match args.get(1) {
+        Some(compare) => {
+            let mut func = expect_function(compare)?;
+            func.set_settings(settings);
+            merge_sort(items, &mut |a, b| {
+                match func.execute(vec![a.clone(), b.clone()], None)? {
+                    ChValue::Number(ChNumber {
+                        value: NumberType::Int(v),
+                        ..
+                    }) => Ok(v < 0),
+                    ChValue::Number(ChNumber {
+                        value: NumberType::Float(v),
+                        ..
+                    }) => Ok(v < 0.0),
+                    ChValue::Bool(b) => Ok(b.value),
+                    other => Err(Error::runtime_at(
+                        format!("sort: compare has to return a Number or Bool, found: {}", other.type_name()),
+                        other.get_start(),
+                        other.get_end(),
+                    )),
+                }
+            })?
+        }
+        None => merge_sort(items, &mut |a, b| match (a, b) {
+            (ChValue::Number(_), ChValue::Number(_)) => Ok(a.clone().less(b.clone())?.is_true()),
+            (ChValue::String(a), ChValue::String(b)) => Ok(a.string < b.string),
+            _ => Err(Error::runtime_at(
+                format!(
+                    "sort: cannot compare {} and {} without a compare function",
+                    a.type_name(),
+                    b.type_name()
+                ),
+                array.start_pos,
+                array.end_pos,
+            )),
+        })?,
+    };
+
+    Ok(ChArray::new(sorted, array.start_pos, array.end_pos).into_type())
+}
+
+//a new array or string, strings are reversed by chars
+fn ch_reverse(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 1)?;
+
+    match &args[0] {
+        ChValue::Array(a) => {
+            let data = a.data.borrow().iter().rev().cloned().collect();
+            Ok(ChArray::new(data, a.start_pos, a.end_pos).into_type())
+        }
+        ChValue::String(s) => Ok(ChValue::String(ChString {
+            string: s.string.chars().rev().collect(),
+            start_pos: s.start_pos,
+            end_pos: s.end_pos,
+        })),
+        other => Err(Error::runtime_at(
+            format!("expected Array or String found: {}", other.type_name()),
+            other.get_start(),
+            other.get_end(),
+        )),
+    }
+}
+
+type LessFn<'a> = dyn FnMut(&ChValue, &ChValue) -> Result<bool, Error> + 'a;
+
+//stable, unlike slice::sort_by a compare function that is not a consistent
+//ordering can not make it panic
+fn merge_sort(mut items: Vec<ChValue>, less: &mut LessFn) -> Result<Vec<ChValue>, Error> {
+    if items.len() <= 1 {
+        return Ok(items);
+    }
+
+    let right = items.split_off(items.len() / 2);
+    let mut left = merge_sort(items, less)?.into_iter().peekable();
+    let mut right = merge_sort(right, less)?.into_iter().peekable();
+
+    let mut merged = Vec::new();
+    while let (Some(l), Some(r)) = (left.peek(), right.peek()) {
+        if less(r, l)? {
+            merged.extend(right.next());
+        } else {
+            merged.extend(left.next());
+        }
+    }
+    merged.extend(left);
+    merged.extend(right);
+    Ok(merged)
+}
+
+fn string_predicate(args: Vec<ChValue>, predicate: fn(&str, &str) -> bool) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 2)?;
+    let string = expect_string(&args[0])?;
+    let pattern = expect_string(&args[1])?;
+
+    Ok(ChValue::Bool(ChBool {
+        value: predicate(string, pattern),
+        start_pos: args[0].get_start(),
+        end_pos: args[1].get_end(),
+    }))
+}
+
+fn ch_contains(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    string_predicate(args, |s, p| s.contains(p))
+}
+
+fn ch_starts_with(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    string_predicate(args, |s, p| s.starts_with(p))
+}
+
+fn ch_ends_with(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    string_predicate(args, |s, p| s.ends_with(p))
+}
+
+fn expect_number(arg: &ChValue) -> Result<&NumberType, Error> {
+    match arg {
+        ChValue::Number(n) => Ok(&n.value),
+        _ => Err(Error::runtime_at(
+            format!("expected Number found: {}", arg.type_name()),
+            arg.get_start(),
+            arg.get_end(),
+        )),
+    }
+}
+
+fn number_like(value: NumberType, arg: &ChValue) -> ChValue {
+    ChValue::Number(ChNumber {
+        value,
+        start_pos: arg.get_start(),
+        end_pos: arg.get_end(),
+        literal: None,
+    })
+}
+
+//applies int_op or float_op to the only argument, ints stay ints
+fn map_number(
+    args: Vec<ChValue>,
+    int_op: fn(ChInt) -> Option<ChInt>,
+    float_op: fn(ChFloat) -> ChFloat,
+) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 1)?;
+    let arg = &args[0];
+
+    let value = match *expect_number(arg)? {
+        NumberType::Int(v) => NumberType::Int(int_op(v).ok_or_else(|| {
+            Error::runtime_at(
+                format!("the result for {} does not fit in an Int", arg),
+                arg.get_start(),
+                arg.get_end(),
+            )
+        })?),
+        NumberType::Float(v) => NumberType::Float(float_op(v)),
+    };
+    Ok(number_like(value, arg))
+}
+
+fn ch_abs(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    map_number(args, ChInt::checked_abs, ChFloat::abs)
+}
+
+fn ch_floor(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    map_number(args, Some, ChFloat::floor)
+}
+
+fn ch_ceil(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    map_number(args, Some, ChFloat::ceil)
+}
+
+fn ch_round(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    map_number(args, Some, ChFloat::round)
+}
+
+fn ch_sqrt(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 1)?;
+    let arg = &args[0];
+
+    let v = match *expect_number(arg)? {
+        NumberType::Int(v) => v as ChFloat,
+        NumberType::Float(v) => v,
+    };
+
+    if v < 0.0 {
+        return Err(Error::runtime_at(
+            format!("cannot take the square root of a negative number: {}", arg),
+            arg.get_start(),
+            arg.get_end(),
+        ));
+    }
+    Ok(number_like(NumberType::Float(v.sqrt()), arg))
+}
+
+fn number_less(a: &NumberType, b: &NumberType) -> bool {
+    match (a, b) {
+        (NumberType::Int(a), NumberType::Int(b)) => a < b,
+        (NumberType::Int(a), NumberType::Float(b)) => (*a as ChFloat) < *b,
+        (NumberType::Float(a), NumberType::Int(b)) => *a < *b as ChFloat,
+        (NumberType::Float(a), NumberType::Float(b)) => a < b,
+    }
+}
+
+//returns the first argument that no other argument is preferred over
+fn pick_number(args: Vec<ChValue>, prefer: fn(&NumberType, &NumberType) -> bool) -> Result<ChValue, Error> {
+    if args.len() < 2 {
+        return Err(Error::runtime(format!(
+            "Expected at least 2 arguments found: {}",
+            args.len()
+        )));
+    }
+
+    let mut best = 0;
+    for i in 0..args.len() {
+        if prefer(expect_number(&args[i])?, expect_number(&args[best])?) {
+            best = i;
+        }
+    }
+    Ok(args.into_iter().nth(best).unwrap())
+}
+
+fn ch_min(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    pick_number(args, number_less)
+}
+
+fn ch_max(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    pick_number(args, |a, b| number_less(b, a))
+}
+
+//elements that are not strings are joined as they would be printed
+fn ch_join(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 2)?;
+    let array = expect_array(&args[0])?;
+    let separator = expect_string(&args[1])?;
+
+    let string = array
+        .data
+        .borrow()
+        .iter()
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(separator);
+
+    Ok(ChValue::String(ChString {
+        string,
+        start_pos: args[0].get_start(),
+        end_pos: args[1].get_end(),
+    }))
+}
+
+//maps are ordered by key, so keys and values always line up
+fn ch_keys(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 1)?;
+    let map = expect_map(&args[0])?;
+
+    let keys = map
+        .data
+        .borrow()
+        .keys()
+        .map(|key| {
+            ChValue::String(ChString {
+                string: key.clone(),
+                start_pos: None,
+                end_pos: None,
+            })
+        })
+        .collect();
+
+    Ok(ChArray::new(keys, map.start_pos, map.end_pos).into_type())
+}
+
+fn ch_values(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 1)?;
+    let map = expect_map(&args[0])?;
+    let values = map.data.borrow().values().cloned().collect();
+
+    Ok(ChArray::new(values, map.start_pos, map.end_pos).into_type())
+}
+
+//[[0, a], [1, b], ...] for an array, a string gives its chars
+fn ch_enumerate(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 1)?;
+
+    let items: Vec<ChValue> = match &args[0] {
+        ChValue::Array(a) => a.data.borrow().clone(),
+        ChValue::String(s) => s
+            .string
+            .chars()
+            .map(|c| {
+                ChValue::String(ChString {
+                    string: c.to_string(),
+                    start_pos: None,
+                    end_pos: None,
+                })
+            })
+            .collect(),
+        other => {
+            return Err(Error::runtime_at(
+                format!("expected Array or String found: {}", other.type_name()),
+                other.get_start(),
+                other.get_end(),
+            ))
+        }
+    };
+
+    let pairs = items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| {
+            let index = ChValue::Number(ChNumber {
+                value: (i as ChInt).get_number_type(),
+                start_pos: None,
+                end_pos: None,
+                literal: None,
+            });
+            ChArray::new(vec![index, item], None, None).into_type()
+        })
+        .collect();
+
+    Ok(ChArray::new(pairs, args[0].get_start(), args[0].get_end()).into_type())
+}
+
+fn int_value(value: ChInt, arg: &ChValue) -> ChValue {
+    ChValue::Number(ChNumber {
+        value: value.get_number_type(),
+        start_pos: arg.get_start(),
+        end_pos: arg.get_end(),
+        literal: None,
+    })
+}
+
+//bits set in the two's complement representation, popcount(-1) is 32
+fn ch_popcount(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 1)?;
+    let n = expect_int(&args[0])?;
+
+    Ok(int_value(n.count_ones() as ChInt, &args[0]))
+}
+
+fn ch_leading_zeros(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 1)?;
+    let n = expect_int(&args[0])?;
+
+    Ok(int_value(n.leading_zeros() as ChInt, &args[0]))
+}
+
+//always positive, gcd(0, 0) is 0
+fn ch_gcd(args: Vec<ChValue>, _name: Option<String>, _settings: &Settings) -> Result<ChValue, Error> {
+    expect_arg_count(&args, 2)?;
+    let mut a = expect_int(&args[0])?.unsigned_abs();
+    let mut b = expect_int(&args[1])?.unsigned_abs();
+
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+
+    match ChInt::try_from(a) {
+        Ok(gcd) => Ok(int_value(gcd, &args[0])),
+        Err(_) => Err(Error::runtime_at(
+            format!("gcd: {} does not fit into an Int", a),
+            args[0].get_start(),
+            args[1].get_end(),
         )),
     }
 }
@@ -394,16 +1811,22 @@ pub struct File {
     pub text: String,
 }
 
+#[derive(Debug, Default)]
 pub struct FileManager {
     pub files: Vec<File>,
     currnet_file: String,
+    current_file_nr: usize,
     current_line: usize,
     current_index: usize,
+    //canonical paths of the imports that are being interpreted and of the
+    //ones that are done, every file is only imported once
+    pub importing: Vec<PathBuf>,
+    pub imported: HashSet<PathBuf>,
 }
 
 impl FileManager {
     pub fn new() -> Self {
-        FileManager { currnet_file: String::from(""), files: Vec::new(), current_line: 0, current_index: 0 }
+        FileManager::default()
     }
 
     pub fn add_file(&mut self, name: String, text: String) {
@@ -411,6 +1834,7 @@ impl FileManager {
 
         let file = File { name, text };
         self.files.push(file);
+        self.current_file_nr = self.files.len() - 1;
         self.current_line = 0;
         self.current_index = 0;
     }
@@ -419,56 +1843,77 @@ impl FileManager {
         if self.files.is_empty() || self.currnet_file != file_name {
             self.add_file(file_name, line);
         } else {
-            let last = self.files.last_mut().unwrap();
-            self.current_index = last.text.len();
-            last.text += &line;
-            self.current_line += 1;
+            let current = &mut self.files[self.current_file_nr];
+            self.current_index = current.text.len();
+            self.current_line = current.text.matches('\n').count();
+            current.text += &line;
         }
     }
+
+    //unlike add_file the following add_line calls still continue the
+    //current file, returns the file_nr of the import
+    pub fn add_import(&mut self, name: String, text: String) -> usize {
+        self.files.push(File { name, text });
+        self.files.len() - 1
+    }
+}
+
+//text starts at line line_nr and byte index_nr of the file file_nr
+pub fn lex(
+    settings: &Settings,
+    file_nr: usize,
+    line_nr: usize,
+    index_nr: usize,
+    text: String,
+) -> Result<Vec<Token>, Error> {
+    let mut lexer = Lexer::new(file_nr, line_nr, index_nr, text);
+    lexer.set_preserve_literals(settings.preserve_literals);
+    lexer.set_keep_comments(settings.keep_comments);
+    lexer.set_emit_newlines(true);
+    lexer.parse_tokens()
+}
+
+pub enum InterpretOutcome {
+    Complete(ChValue),
+    NeedMoreInput,
+    Error(Error),
 }
 
 pub struct Compiler {
     pub global_scope: Rc<RefCell<Scope>>,
-    pub file_manager: FileManager,
-    //pub files: Vec<(String, String, usize)>,
-    //pub current_file: String,
+    pending_input: String,
 }
 
 impl Compiler {
     pub fn new() -> Self {
-        let mut table = SymbolTable::default();
-        let mut fm = FileManager::new();
-        fm.add_file(String::from("<rust>"), String::from(""));
+        Compiler::with_globals(SymbolTable::builtins())
+    }
 
-        table.set(&String::from("false"), ChValue::Bool(ChBool::from(false)));
-        table.set(&String::from("true"), ChValue::Bool(ChBool::from(true)));
-        table.set(
-            &String::from("none"),
-            ChValue::None(ChNone {
-                start_pos: None,
-                end_pos: None,
-            }),
-        );
+    //the default globals plus additional rust functions
+    pub fn with_builtins(extra: Vec<(String, RustFunc)>) -> Self {
+        let mut table = SymbolTable::builtins();
 
-        table.set(
-            &String::from("print"),
-            ChValue::Function(ChFunction {
-                func_type: FuncType::RustFunc(RustFunc {
-                    name: "print[args...]".to_string(),
-                    function: ch_print,
+        for (name, func) in extra {
+            table.set(
+                &name,
+                ChValue::Function(ChFunction {
+                    func_type: FuncType::RustFunc(func),
                 }),
-            }),
-        );
+            );
+        }
 
-        table.set(
-            &String::from("len"),
-            ChValue::Function(ChFunction {
-                func_type: FuncType::RustFunc(RustFunc {
-                    name: "len[arg]".to_string(),
-                    function: ch_len,
-                }),
-            }),
-        );
+        Compiler::with_globals(table)
+    }
+
+    //uses table as the complete set of globals, see SymbolTable::builtins
+    pub fn with_globals(table: SymbolTable) -> Self {
+        let mut fm = FileManager::new();
+        fm.add_file(String::from("<rust>"), String::from(""));
+
+        let settings = Settings {
+            files: Rc::new(RefCell::new(fm)),
+            ..Settings::default()
+        };
 
         Compiler {
             global_scope: Rc::new(RefCell::new(Scope {
@@ -476,23 +1921,165 @@ impl Compiler {
                 parent: None,
                 position: None,
                 symbol_table: table,
+                settings: Rc::new(settings),
+                caller: None,
             })),
-            file_manager: fm,
+            pending_input: String::new(),
+        }
+    }
+
+    //everything interpreted so far including imports, for Error::set_files
+    pub fn files(&self) -> Vec<File> {
+        self.global_scope.borrow().settings.files.borrow().files.clone()
+    }
+
+    //scopes created after this call inherit the mode from their parent
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        let hasher = if deterministic {
+            SymbolHasher::Fixed
+        } else {
+            SymbolHasher::default()
+        };
+
+        self.global_scope.borrow_mut().symbol_table.rehash(hasher);
+    }
+
+    //off by default, panics should not be hidden while developing rust functions
+    pub fn set_catch_panics(&mut self, catch_panics: bool) {
+        Rc::make_mut(&mut self.global_scope.borrow_mut().settings).catch_panics = catch_panics;
+    }
+
+    pub fn set_preserve_literals(&mut self, preserve_literals: bool) {
+        Rc::make_mut(&mut self.global_scope.borrow_mut().settings).preserve_literals = preserve_literals;
+    }
+
+    pub fn set_keep_comments(&mut self, keep_comments: bool) {
+        Rc::make_mut(&mut self.global_scope.borrow_mut().settings).keep_comments = keep_comments;
+    }
+
+    pub fn set_strict(&mut self, strict: bool) {
+        Rc::make_mut(&mut self.global_scope.borrow_mut().settings).strict = strict;
+    }
+
+    //100 by default, which fits the 8 MiB stack of a main thread even in debug
+    //builds, where a call takes roughly 25-40 KiB of stack. Raise it only when
+    //interpreting on a thread with a bigger stack (about 64 KiB per call)
+    pub fn set_max_call_depth(&mut self, max_call_depth: usize) {
+        Rc::make_mut(&mut self.global_scope.borrow_mut().settings).max_call_depth = max_call_depth;
+    }
+
+    //bounds the work of untrusted scripts, None (the default) is unbounded
+    pub fn set_step_limit(&mut self, step_limit: Option<u64>) {
+        Rc::make_mut(&mut self.global_scope.borrow_mut().settings).step_limit = step_limit;
+    }
+
+    //print writes to output instead of stdout, e.g. an Rc<RefCell<Vec<u8>>>
+    //to capture it
+    pub fn set_output(&mut self, output: Rc<RefCell<dyn Write>>) {
+        Rc::make_mut(&mut self.global_scope.borrow_mut().settings).output = Output(output);
+    }
+
+    fn reset_steps(&self) {
+        self.global_scope.borrow().settings.steps.set(0);
+    }
+
+    //buffers lines until all brackets are closed, then interprets them as one chunk
+    pub fn interpret_line(&mut self, line: &str) -> InterpretOutcome {
+        self.pending_input += line;
+
+        if is_incomplete(&self.pending_input) {
+            return InterpretOutcome::NeedMoreInput;
+        }
+
+        let input = mem::take(&mut self.pending_input);
+        match self.interpret(String::from("<stdin>"), input) {
+            Ok(value) => InterpretOutcome::Complete(value),
+            Err(e) => InterpretOutcome::Error(e),
+        }
+    }
+
+    //adds text to the file manager, so that errors on the tokens can be printed
+    pub fn tokenize(&mut self, file_name: String, text: String) -> Result<Vec<Token>, Error> {
+        let settings = self.global_scope.borrow().settings.clone();
+
+        let (file_nr, line_nr, index_nr) = {
+            let mut fm = settings.files.borrow_mut();
+            fm.add_line(text.clone(), file_name);
+            (fm.current_file_nr, fm.current_line, fm.current_index)
+        };
+
+        lex(&settings, file_nr, line_nr, index_nr, text)
+    }
+
+    //the tokens have to end in Eof like the ones from tokenize, the parser
+    //relies on it to stop
+    pub fn parse(tokens: Vec<Token>) -> Result<Node, Error> {
+        match tokens.last() {
+            Some(Token { token_type: TokenType::Eof, .. }) => Parser::new(tokens).parse(),
+            last => Err(Error::new(
+                ErrType::InvalidSyntax,
+                last.map(|t| t.start_pos),
+                last.map(|t| t.end_pos),
+                String::from("Parser: expected the tokens to end with EOF"),
+                None,
+            )),
         }
     }
 
     pub fn interpret(&mut self, file_name: String, line: String) -> Result<ChValue, Error> {
-        self.file_manager.add_line(line.clone(), file_name);
+        self.reset_steps();
+        let result = self
+            .tokenize(file_name, line)
+            .and_then(Compiler::parse)
+            .and_then(|mut ast| visit_node(&mut ast, &mut self.global_scope));
 
-        let index_nr = self.file_manager.current_index;
-        let file_nr = self.file_manager.files.len() - 1;
-        let line_nr = self.file_manager.current_line;
+        result.map_err(|e| self.with_module_frame(e))
+    }
+
+    //like interpret, but runs the checks from lint.rs on the AST first
+    pub fn interpret_with_warnings(
+        &mut self,
+        file_name: String,
+        line: String,
+    ) -> Result<(ChValue, Vec<Warning>), Error> {
+        self.reset_steps();
+        let result = self
+            .tokenize(file_name, line)
+            .and_then(Compiler::parse)
+            .and_then(|mut ast| {
+                let warnings = check(&ast);
+                visit_node(&mut ast, &mut self.global_scope).map(|value| (value, warnings))
+            });
+
+        result.map_err(|e| self.with_module_frame(e))
+    }
 
-        let mut lexer = Lexer::new(file_nr, line_nr, index_nr, line);
-        let tokens = lexer.parse_tokens()?;
-        let mut parser = Parser::new(tokens);
-        let mut ast = parser.parse()?;
+    //calls a global function from rust, locals of the call are dropped afterwards
+    //but changes to globals stay, like a call from the script itself
+    pub fn call_function(&mut self, name: &str, args: Vec<ChValue>) -> Result<ChValue, Error> {
+        self.reset_steps();
+        let value = self.global_scope.borrow().get(name);
 
-        visit_node(&mut ast, &mut self.global_scope)
+        let result = match value {
+            Some(ChValue::Function(mut func)) => {
+                func.set_scope(self.global_scope.clone());
+                func.execute(args, Some(name.to_string()))
+            }
+            Some(other) => Err(Error::runtime(format!(
+                "expected Function found {}",
+                other.type_name()
+            ))),
+            None => Err(Error::runtime(format!("\"{}\" is not defined", name))),
+        };
+
+        result.map_err(|e| self.with_module_frame(e))
+    }
+
+    //every error gets at least the <module> frame in its traceback
+    fn with_module_frame(&self, mut e: Error) -> Error {
+        if !e.has_scope() {
+            e.set_scope(self.global_scope.clone());
+        }
+        e
     }
 }
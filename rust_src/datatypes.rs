@@ -1,7 +1,7 @@
 use crate::chronos::*;
 use crate::errors::*;
 use crate::interpreter::visit_node;
-use std::{cell::RefCell, fmt, fmt::Debug, fmt::Display, rc::Rc};
+use std::{cell::RefCell, collections::BTreeMap, fmt, fmt::Debug, fmt::Display, rc::Rc};
 
 pub trait HasPosition {
     fn get_start(&self) -> Option<Position>;
@@ -17,33 +17,11 @@ pub trait IsFunction {
     fn execute(&mut self, args: Vec<ChValue>, name: Option<String>) -> Result<ChValue, Error>;
 }
 
+//rust values that are stored as a NumberType, conversions between
+//chronos values are done by ChValue::convert_to_*
 pub trait ConvertValue {
-    fn into_number_type(self) -> NumberType
-    where
-        Self: Sized,
-    {
-        panic!("value can't be converted");
-    }
-
-    fn convert_to_number(self) -> Result<NumberType, Error>
-    where
-        Self: Sized + HasPosition + Display,
-    {
-        Err(Error::new(
-            ErrType::Runtime,
-            self.get_start(),
-            self.get_end(),
-            format!("could not convert {} to Number", self),
-            None,
-        ))
-    }
-
-    fn get_number_type(&self) -> NumberType
-    where
-        Self: Sized,
-    {
-        panic!("value can't be converted");
-    }
+    fn into_number_type(self) -> NumberType;
+    fn get_number_type(&self) -> NumberType;
 }
 
 fn generate_undefined_op(caller: &dyn IsChValue, op_name: &str) -> Result<ChValue, Error> {
@@ -60,6 +38,53 @@ fn generate_undefined_op(caller: &dyn IsChValue, op_name: &str) -> Result<ChValu
     ))
 }
 
+//the range of a slice into len elements, negative bounds count from the end
+//and bounds outside of 0..len are clamped
+fn slice_range(len: usize, start: Option<ChInt>, end: Option<ChInt>) -> (usize, usize) {
+    let clamp = |i: ChInt| {
+        if i < 0 {
+            len.saturating_sub(i.unsigned_abs() as usize)
+        } else {
+            (i as usize).min(len)
+        }
+    };
+
+    let start = start.map_or(0, clamp);
+    let end = end.map_or(len, clamp);
+    (start, end.max(start))
+}
+
+//'"ab" * 3' and '[0] * 3', the count has to be a non-negative Int
+fn repeat_count(caller: &dyn IsChValue, other: &ChValue) -> Result<usize, Error> {
+    let count = match other {
+        ChValue::Number(ChNumber {
+            value: NumberType::Int(v),
+            ..
+        }) => *v,
+        ChValue::Bool(b) => b.value as ChInt,
+        _ => {
+            return Err(Error::runtime_at(
+                format!(
+                    "{} can only be multiplied by an Int, found: {} '{}'",
+                    caller.get_desc(),
+                    other.type_name(),
+                    other
+                ),
+                caller.get_start(),
+                other.get_end().or(caller.get_end()),
+            ))
+        }
+    };
+
+    usize::try_from(count).map_err(|_| {
+        Error::runtime_at(
+            format!("cannot repeat {} a negative number of times: {}", caller.get_desc(), count),
+            caller.get_start(),
+            other.get_end().or(caller.get_end()),
+        )
+    })
+}
+
 pub trait ChOperators {
     fn add(self, _other: ChValue) -> Result<ChValue, Error>
     where
@@ -97,6 +122,12 @@ pub trait ChOperators {
     {
         generate_undefined_op(&self, "div")
     }
+    fn modulo(self, _other: ChValue) -> Result<ChValue, Error>
+    where
+        Self: IsChValue + Sized,
+    {
+        generate_undefined_op(&self, "modulo")
+    }
     fn pow(self, _other: ChValue) -> Result<ChValue, Error>
     where
         Self: IsChValue + Sized,
@@ -139,34 +170,34 @@ pub trait ChOperators {
     {
         generate_undefined_op(&self, "greater equal")
     }
-    fn and(self, _other: ChValue) -> Result<ChValue, Error>
+    fn not(self) -> Result<ChValue, Error>
     where
         Self: IsChValue + Sized,
     {
-        generate_undefined_op(&self, "and")
+        generate_undefined_op(&self, "not")
     }
-    fn or(self, _other: ChValue) -> Result<ChValue, Error>
+
+    fn negate(self) -> Result<ChValue, Error>
     where
         Self: IsChValue + Sized,
     {
-        generate_undefined_op(&self, "or")
+        generate_undefined_op(&self, "negate")
     }
-    fn not(self) -> Result<ChValue, Error>
+
+    //unary +
+    fn identity(self) -> Result<ChValue, Error>
     where
         Self: IsChValue + Sized,
     {
-        generate_undefined_op(&self, "not")
-    }
-
-    fn is_true(&self) -> bool {
-        false
+        generate_undefined_op(&self, "unary +")
     }
 
-    fn negate(self) -> Result<ChValue, Error>
+    //value[start:end], a missing bound is None
+    fn slice(&self, _start: Option<ChInt>, _end: Option<ChInt>) -> Result<ChValue, Error>
     where
         Self: IsChValue + Sized,
     {
-        generate_undefined_op(&self, "negate")
+        generate_undefined_op(self, "[:]")
     }
 
     fn access(&self, _other: ChValue) -> Result<ChValue, Error>
@@ -177,7 +208,7 @@ pub trait ChOperators {
     }
 }
 
-pub trait IsChValue: Display + HasPosition + HasScope + ChOperators + ConvertValue {
+pub trait IsChValue: Display + HasPosition + HasScope + ChOperators {
     fn get_desc(&self) -> String;
     fn into_type(self) -> ChValue;
 }
@@ -189,6 +220,7 @@ pub enum ChValue {
     Number(ChNumber),
     String(ChString),
     Array(ChArray),
+    Map(ChMap),
     Function(ChFunction),
     Bool(ChBool),
     None(ChNone),
@@ -211,6 +243,7 @@ macro_rules! unwrap_chvalue {
             ChValue::String($inner) => $e,
             ChValue::Bool($inner) => $e,
             ChValue::Array($inner) => $e,
+            ChValue::Map($inner) => $e,
             ChValue::Function($inner) => $e,
             ChValue::None($inner) => $e,
         }
@@ -225,6 +258,20 @@ impl Display for ChValue {
     }
 }
 
+impl ChValue {
+    pub fn type_name(&self) -> String {
+        unwrap_chvalue!(self, e, e.get_desc())
+    }
+
+    //how the repl shows a result, e.g. '3 : Number'
+    pub fn repl_display(&self) -> String {
+        match self {
+            ChValue::None(_) => self.to_string(),
+            _ => format!("{} : {}", self, self.type_name()),
+        }
+    }
+}
+
 impl HasPosition for ChValue {
     fn get_start(&self) -> Option<Position> {
         unwrap_chvalue!(self, e, e.get_start())
@@ -239,23 +286,62 @@ impl HasPosition for ChValue {
     }
 }
 
+//------------- Conversions ------------------------//
+
+//every implicit conversion between chronos types is decided here:
+//
+//             to Number   to String   to Bool
+//  Number     itself      printed     != 0
+//  String     error       itself      not empty
+//  Bool       0 or 1      error       itself
+//  None       error       error       false
+//  Function   error       error       true
+//  Array      error       error       not empty
+//  Map        error       error       not empty
 impl ChValue {
-    pub fn is_true(&self) -> bool {
-        unwrap_chvalue!(self, e, e.is_true())
+    pub fn convert_to_number(&self) -> Result<NumberType, Error> {
+        match self {
+            ChValue::Number(n) => Ok(n.value.clone()),
+            ChValue::Bool(b) => Ok(b.value.into_number_type()),
+            _ => Err(self.conversion_error("Number")),
+        }
     }
-}
 
-impl ConvertValue for ChValue {
-    fn into_number_type(self) -> NumberType {
-        unwrap_chvalue!(self, e, e.into_number_type())
+    pub fn convert_to_string(&self) -> Result<String, Error> {
+        match self {
+            ChValue::Number(n) => Ok(n.to_string()),
+            ChValue::String(s) => Ok(s.string.clone()),
+            _ => Err(self.conversion_error("String")),
+        }
+    }
+
+    pub fn convert_to_bool(&self) -> bool {
+        match self {
+            ChValue::Number(n) => match n.value {
+                NumberType::Int(v) => v != 0,
+                NumberType::Float(v) => v != 0.0,
+            },
+            ChValue::String(s) => !s.string.is_empty(),
+            ChValue::Bool(b) => b.value,
+            ChValue::None(_) => false,
+            ChValue::Function(_) => true,
+            ChValue::Array(a) => !a.is_empty(),
+            ChValue::Map(m) => !m.is_empty(),
+        }
     }
 
-    fn convert_to_number(self) -> Result<NumberType, Error> {
-        unwrap_chvalue!(self, e, e.convert_to_number())
+    pub fn is_true(&self) -> bool {
+        self.convert_to_bool()
     }
 
-    fn get_number_type(&self) -> NumberType {
-        unwrap_chvalue!(self, e, e.get_number_type())
+    fn conversion_error(&self, target: &str) -> Error {
+        Error::new(
+            ErrType::Runtime,
+            self.get_start(),
+            self.get_end(),
+            format!("could not convert {} '{}' to {}", self.type_name(), self, target),
+            None,
+        )
     }
 }
 
@@ -276,6 +362,10 @@ impl ChOperators for ChValue {
         unwrap_chvalue!(self, e, e.div(other))
     }
 
+    fn modulo(self, other: ChValue) -> Result<ChValue, Error> {
+        unwrap_chvalue!(self, e, e.modulo(other))
+    }
+
     fn pow(self, other: ChValue) -> Result<ChValue, Error> {
         unwrap_chvalue!(self, e, e.pow(other))
     }
@@ -312,34 +402,45 @@ impl ChOperators for ChValue {
         unwrap_chvalue!(self, e, e.not_equal(other))
     }
 
-    fn and(self, other: ChValue) -> Result<ChValue, Error> {
-        unwrap_chvalue!(self, e, e.and(other))
-    }
-
-    fn or(self, other: ChValue) -> Result<ChValue, Error> {
-        unwrap_chvalue!(self, e, e.or(other))
-    }
-
     fn not(self) -> Result<ChValue, Error> {
         unwrap_chvalue!(self, e, e.not())
     }
 
-    fn is_true(&self) -> bool {
-        unwrap_chvalue!(self, e, e.is_true())
-    }
-
     fn negate(self) -> Result<ChValue, Error> {
         unwrap_chvalue!(self, e, e.negate())
     }
 
+    fn identity(self) -> Result<ChValue, Error> {
+        unwrap_chvalue!(self, e, e.identity())
+    }
+
     fn access(&self, other: ChValue) -> Result<ChValue, Error> {
         unwrap_chvalue!(self, e, e.access(other))
     }
+
+    fn slice(&self, start: Option<ChInt>, end: Option<ChInt>) -> Result<ChValue, Error> {
+        unwrap_chvalue!(self, e, e.slice(start, end))
+    }
+}
+
+impl PartialEq for ChValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ChValue::Number(v1), ChValue::Number(v2)) => v1 == v2,
+            (ChValue::String(v1), ChValue::String(v2)) => v1 == v2,
+            (ChValue::Array(v1), ChValue::Array(v2)) => v1 == v2,
+            (ChValue::Map(v1), ChValue::Map(v2)) => v1 == v2,
+            (ChValue::Function(v1), ChValue::Function(v2)) => v1 == v2,
+            (ChValue::Bool(v1), ChValue::Bool(v2)) => v1 == v2,
+            (ChValue::None(v1), ChValue::None(v2)) => v1 == v2,
+            _ => false,
+        }
+    }
 }
 
 impl IsChValue for ChValue {
     fn get_desc(&self) -> String {
-        unwrap_chvalue!(self, e, e.get_desc())
+        self.type_name()
     }
 
     fn into_type(self) -> ChValue {
@@ -357,6 +458,12 @@ pub struct ChNone {
 
 impl HasScope for ChNone {}
 
+impl PartialEq for ChNone {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
 impl HasPosition for ChNone {
     fn get_start(&self) -> Option<Position> {
         self.start_pos
@@ -391,9 +498,6 @@ impl ChOperators for ChNone {
         .into_type())
     }
 
-    fn is_true(&self) -> bool {
-        false
-    }
 }
 
 impl IsChValue for ChNone {
@@ -406,18 +510,6 @@ impl IsChValue for ChNone {
     }
 }
 
-impl ConvertValue for ChNone {
-    fn convert_to_number(self) -> Result<NumberType, Error> {
-        Err(Error::new(
-            ErrType::Runtime,
-            self.get_start(),
-            self.get_end(),
-            String::from("can not convert 'none' to Numbertype"),
-            None,
-        ))
-    }
-}
-
 impl Display for ChNone {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "none")
@@ -441,19 +533,16 @@ impl ChBool {
             end_pos: None,
         }
     }
-}
-
-impl ConvertValue for ChBool {
-    fn into_number_type(self) -> NumberType {
-        NumberType::Int(self.value as i32)
-    }
-
-    fn convert_to_number(self) -> Result<NumberType, Error> {
-        Ok(NumberType::Int(self.value as i32))
-    }
 
-    fn get_number_type(&self) -> NumberType {
-        NumberType::Int(self.value as i32)
+    //arithmetic and ordering use the Int 0 or 1, the same conversion a Number
+    //applies to a Bool on its right side, so 'true + 1' == '1 + true'
+    fn as_number(&self) -> ChNumber {
+        ChNumber {
+            value: self.value.into_number_type(),
+            start_pos: self.start_pos,
+            end_pos: self.end_pos,
+            literal: None,
+        }
     }
 }
 
@@ -469,6 +558,12 @@ impl IsChValue for ChBool {
 
 impl HasScope for ChBool {}
 
+impl PartialEq for ChBool {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
 impl Display for ChBool {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", if self.value { "true" } else { "false" })
@@ -491,10 +586,35 @@ impl HasPosition for ChBool {
 }
 
 impl ChOperators for ChBool {
+    fn add(self, other: ChValue) -> Result<ChValue, Error> {
+        self.as_number().add(other)
+    }
+
+    fn sub(self, other: ChValue) -> Result<ChValue, Error> {
+        self.as_number().sub(other)
+    }
+
+    fn mult(self, other: ChValue) -> Result<ChValue, Error> {
+        self.as_number().mult(other)
+    }
+
+    fn div(self, other: ChValue) -> Result<ChValue, Error> {
+        self.as_number().div(other)
+    }
+
+    fn modulo(self, other: ChValue) -> Result<ChValue, Error> {
+        self.as_number().modulo(other)
+    }
+
+    fn pow(self, other: ChValue) -> Result<ChValue, Error> {
+        self.as_number().pow(other)
+    }
+
     fn equal(self, other: ChValue) -> Result<ChValue, Error> {
         Ok(ChBool {
             value: match other {
                 ChValue::Bool(b) => self.value == b.value,
+                ChValue::Number(n) => self.as_number() == n,
                 _ => false,
             },
             start_pos: self.start_pos,
@@ -507,6 +627,7 @@ impl ChOperators for ChBool {
         Ok(ChBool {
             value: match other {
                 ChValue::Bool(b) => self.value != b.value,
+                ChValue::Number(n) => self.as_number() != n,
                 _ => true,
             },
             start_pos: self.start_pos,
@@ -515,31 +636,25 @@ impl ChOperators for ChBool {
         .into_type())
     }
 
-    fn not(mut self) -> Result<ChValue, Error> {
-        self.value = !self.value;
-        Ok(self.into_type())
+    fn less(self, other: ChValue) -> Result<ChValue, Error> {
+        self.as_number().less(other)
     }
 
-    fn is_true(&self) -> bool {
-        self.value
+    fn less_equal(self, other: ChValue) -> Result<ChValue, Error> {
+        self.as_number().less_equal(other)
     }
 
-    fn and(self, other: ChValue) -> Result<ChValue, Error> {
-        Ok(ChBool {
-            value: self.value && other.is_true(),
-            start_pos: self.start_pos,
-            end_pos: self.end_pos,
-        }
-        .into_type())
+    fn greater(self, other: ChValue) -> Result<ChValue, Error> {
+        self.as_number().greater(other)
     }
 
-    fn or(self, other: ChValue) -> Result<ChValue, Error> {
-        Ok(ChBool {
-            value: self.value || other.is_true(),
-            start_pos: self.start_pos,
-            end_pos: self.end_pos,
-        }
-        .into_type())
+    fn greater_equal(self, other: ChValue) -> Result<ChValue, Error> {
+        self.as_number().greater_equal(other)
+    }
+
+    fn not(mut self) -> Result<ChValue, Error> {
+        self.value = !self.value;
+        Ok(self.into_type())
     }
 }
 
@@ -551,15 +666,35 @@ pub enum NumberType {
     Float(ChFloat),
 }
 
+impl PartialEq for NumberType {
+    #[allow(clippy::float_cmp)]
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (NumberType::Int(v1), NumberType::Int(v2)) => v1 == v2,
+            (NumberType::Float(v1), NumberType::Float(v2)) => v1 == v2,
+            (NumberType::Int(v1), NumberType::Float(v2)) => *v1 as ChFloat == *v2,
+            (NumberType::Float(v1), NumberType::Int(v2)) => *v1 == *v2 as ChFloat,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ChNumber {
     pub value: NumberType,
     pub start_pos: Option<Position>,
     pub end_pos: Option<Position>,
+    //how the number was written, see Settings::preserve_literals
+    pub literal: Option<Rc<str>>,
 }
 
 impl HasScope for ChNumber {}
 
+impl PartialEq for ChNumber {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
 impl IsChValue for ChNumber {
     fn get_desc(&self) -> String {
         String::from("Number")
@@ -572,6 +707,10 @@ impl IsChValue for ChNumber {
 
 impl Display for ChNumber {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(literal) = &self.literal {
+            return write!(f, "{}", literal);
+        }
+
         match self.value {
             NumberType::Int(v) => write!(f, "{}", v),
             NumberType::Float(v) => write!(f, "{}", v),
@@ -579,20 +718,6 @@ impl Display for ChNumber {
     }
 }
 
-impl ConvertValue for ChNumber {
-    fn into_number_type(self) -> NumberType {
-        self.value
-    }
-
-    fn get_number_type(&self) -> NumberType {
-        self.value.clone()
-    }
-
-    fn convert_to_number(self) -> Result<NumberType, Error> {
-        Ok(self.value)
-    }
-}
-
 impl HasPosition for ChNumber {
     fn get_start(&self) -> Option<Position> {
         self.start_pos
@@ -614,24 +739,54 @@ impl ChNumber {
             value,
             start_pos: None,
             end_pos: None,
+            literal: None,
         }
     }
 
+    //numbers compare with numbers and bools, anything else is an error
+    //naming both types instead of a failed conversion
+    fn comparison_operand(&self, other: &ChValue, op_name: &str) -> Result<NumberType, Error> {
+        other.convert_to_number().map_err(|_| {
+            Error::runtime_at(
+                format!(
+                    "cannot compare {} and {} with '{}'",
+                    self.get_desc(),
+                    other.type_name(),
+                    op_name
+                ),
+                self.start_pos,
+                other.get_end().or(self.end_pos),
+            )
+        })
+    }
+
+    //int_op returns None on overflow, which is reported instead of wrapping
     #[rustfmt::skip]
     fn operate_on(
         mut self,
         other: NumberType,
-        int_op: fn(ChInt, ChInt) -> ChInt,
+        op_name: &str,
+        int_op: fn(ChInt, ChInt) -> Option<ChInt>,
         float_op: fn(ChFloat, ChFloat) -> ChFloat,
-    ) -> Self {
+    ) -> Result<Self, Error> {
+        self.literal = None;
         self.value = match (self.value, other) {
-            (NumberType::Int(v1), NumberType::Int(v2)) => NumberType::Int(int_op(v1, v2)),
+            (NumberType::Int(v1), NumberType::Int(v2)) => match int_op(v1, v2) {
+                Some(v) => NumberType::Int(v),
+                None => {
+                    return Err(Error::runtime_at(
+                        format!("integer overflow: {} {} {}", v1, op_name, v2),
+                        self.start_pos,
+                        self.end_pos,
+                    ))
+                }
+            },
             (NumberType::Float(v1), NumberType::Int(v2)) => NumberType::Float(float_op(v1, v2 as ChFloat)),
             (NumberType::Int(v1), NumberType::Float(v2)) => NumberType::Float(float_op(v1 as ChFloat, v2)),
             (NumberType::Float(v1), NumberType::Float(v2)) => NumberType::Float(float_op(v1, v2)),
         };
 
-        self
+        Ok(self)
     }
 }
 
@@ -640,9 +795,10 @@ impl ChOperators for ChNumber {
         Ok(self
             .operate_on(
                 other.convert_to_number()?,
-                |v1: ChInt, v2: ChInt| v1 + v2,
+                "+",
+                |v1: ChInt, v2: ChInt| v1.checked_add(v2),
                 |v1: ChFloat, v2: ChFloat| v1 + v2,
-            )
+            )?
             .into_type())
     }
 
@@ -650,9 +806,10 @@ impl ChOperators for ChNumber {
         Ok(self
             .operate_on(
                 other.convert_to_number()?,
-                |v1: ChInt, v2: ChInt| v1 - v2,
+                "-",
+                |v1: ChInt, v2: ChInt| v1.checked_sub(v2),
                 |v1: ChFloat, v2: ChFloat| v1 - v2,
-            )
+            )?
             .into_type())
     }
 
@@ -660,9 +817,10 @@ impl ChOperators for ChNumber {
         Ok(self
             .operate_on(
                 other.convert_to_number()?,
-                |v1: ChInt, v2: ChInt| v1 * v2,
+                "*",
+                |v1: ChInt, v2: ChInt| v1.checked_mul(v2),
                 |v1: ChFloat, v2: ChFloat| v1 * v2,
-            )
+            )?
             .into_type())
     }
 
@@ -682,43 +840,66 @@ impl ChOperators for ChNumber {
             Ok(self
                 .operate_on(
                     other.convert_to_number()?,
-                    |v1: ChInt, v2: ChInt| v1 / v2,
+                    "/",
+                    |v1: ChInt, v2: ChInt| v1.checked_div(v2),
                     |v1: ChFloat, v2: ChFloat| v1 / v2,
-                )
+                )?
                 .into_type())
         }
     }
 
-    fn pow(mut self, other: ChValue) -> Result<ChValue, Error> {
+    fn modulo(self, other: ChValue) -> Result<ChValue, Error> {
         if match other.clone().convert_to_number()? {
             NumberType::Int(v) => v == 0,
-            _ => false,
+            NumberType::Float(v) => v == 0.0,
         } {
-            self.value = 0.into_number_type();
-            Ok(self.into_type())
+            Err(Error::new(
+                ErrType::Runtime,
+                self.start_pos,
+                self.end_pos,
+                String::from("Modulo by 0"),
+                None,
+            ))
         } else {
             Ok(self
                 .operate_on(
                     other.convert_to_number()?,
-                    |v1: ChInt, v2: ChInt| v1.pow(v2.try_into().unwrap_or(0)),
-                    |v1: ChFloat, v2: ChFloat| v1.powf(v2),
-                )
+                    "%",
+                    |v1: ChInt, v2: ChInt| v1.checked_rem(v2),
+                    |v1: ChFloat, v2: ChFloat| v1 % v2,
+                )?
                 .into_type())
         }
     }
 
-    #[allow(clippy::float_cmp)]
+    fn pow(mut self, other: ChValue) -> Result<ChValue, Error> {
+        let exponent = other.convert_to_number()?;
+
+        //a negative int exponent gives a fraction: 2^-1 == 0.5
+        if let (NumberType::Int(base), NumberType::Int(e)) = (&self.value, &exponent) {
+            if *e < 0 {
+                self.value = NumberType::Float((*base as ChFloat).powf(*e as ChFloat));
+                self.literal = None;
+                return Ok(self.into_type());
+            }
+        }
+
+        Ok(self
+            .operate_on(
+                exponent,
+                "^",
+                |v1: ChInt, v2: ChInt| v1.checked_pow(v2 as u32),
+                |v1: ChFloat, v2: ChFloat| v1.powf(v2),
+            )?
+            .into_type())
+    }
+
     fn equal(self, other: ChValue) -> Result<ChValue, Error> {
         let value = other.convert_to_number();
 
         Ok(ChBool {
             value: match value {
-                Ok(v) => match (self.value, v) {
-                    (NumberType::Int(v1), NumberType::Int(v2)) => v1 == v2,
-                    (NumberType::Float(v1), NumberType::Float(v2)) => v1 == v2,
-                    (NumberType::Int(v1), NumberType::Float(v2)) => v1 as ChFloat == v2,
-                    (NumberType::Float(v1), NumberType::Int(v2)) => v1 == v2 as ChFloat,
-                },
+                Ok(v) => self.value == v,
                 Err(_) => false,
             },
             start_pos: self.start_pos,
@@ -727,19 +908,13 @@ impl ChOperators for ChNumber {
         .into_type())
     }
 
-    #[allow(clippy::float_cmp)]
     fn not_equal(self, other: ChValue) -> Result<ChValue, Error> {
         let value = other.convert_to_number();
 
         Ok(ChBool {
             value: match value {
-                Ok(v) => match (self.value, v) {
-                    (NumberType::Int(v1), NumberType::Int(v2)) => v1 != v2,
-                    (NumberType::Float(v1), NumberType::Float(v2)) => v1 != v2,
-                    (NumberType::Int(v1), NumberType::Float(v2)) => v1 as ChFloat != v2,
-                    (NumberType::Float(v1), NumberType::Int(v2)) => v1 != v2 as ChFloat,
-                },
-                Err(_) => false,
+                Ok(v) => self.value != v,
+                Err(_) => true,
             },
             start_pos: self.start_pos,
             end_pos: self.end_pos,
@@ -748,7 +923,7 @@ impl ChOperators for ChNumber {
     }
 
     fn less(self, other: ChValue) -> Result<ChValue, Error> {
-        let value = other.convert_to_number()?;
+        let value = self.comparison_operand(&other, "<")?;
 
         Ok(ChBool {
             value: match (self.value, value) {
@@ -764,7 +939,7 @@ impl ChOperators for ChNumber {
     }
 
     fn less_equal(self, other: ChValue) -> Result<ChValue, Error> {
-        let value = other.convert_to_number()?;
+        let value = self.comparison_operand(&other, "<=")?;
 
         Ok(ChBool {
             value: match (self.value, value) {
@@ -780,7 +955,7 @@ impl ChOperators for ChNumber {
     }
 
     fn greater(self, other: ChValue) -> Result<ChValue, Error> {
-        let value = other.convert_to_number()?;
+        let value = self.comparison_operand(&other, ">")?;
 
         Ok(ChBool {
             value: match (self.value, value) {
@@ -796,7 +971,7 @@ impl ChOperators for ChNumber {
     }
 
     fn greater_equal(self, other: ChValue) -> Result<ChValue, Error> {
-        let value = other.convert_to_number()?;
+        let value = self.comparison_operand(&other, ">=")?;
 
         Ok(ChBool {
             value: match (self.value, value) {
@@ -811,65 +986,35 @@ impl ChOperators for ChNumber {
         .into_type())
     }
 
-    fn and(self, other: ChValue) -> Result<ChValue, Error> {
-        let value = other.convert_to_number()?;
-
-        Ok(ChBool {
-            value: match (self.value, value) {
-                (NumberType::Int(v1), NumberType::Int(v2)) => v1 >= 1 && v2 >= 1,
-                (NumberType::Float(v1), NumberType::Float(v2)) => v1 >= 1.0 && v2 >= 1.0,
-                (NumberType::Int(v1), NumberType::Float(v2)) => v1 >= 1 && v2 >= 1.0,
-                (NumberType::Float(v1), NumberType::Int(v2)) => v1 >= 1.0 && v2 >= 1,
-            },
-            start_pos: self.start_pos,
-            end_pos: self.end_pos,
-        }
-        .into_type())
-    }
-
-    fn or(self, other: ChValue) -> Result<ChValue, Error> {
-        let value = other.convert_to_number()?;
-
-        Ok(ChBool {
-            value: match (self.value, value) {
-                (NumberType::Int(v1), NumberType::Int(v2)) => v1 >= 1 || v2 >= 1,
-                (NumberType::Float(v1), NumberType::Float(v2)) => v1 >= 1.0 || v2 >= 1.0,
-                (NumberType::Int(v1), NumberType::Float(v2)) => v1 >= 1 || v2 >= 1.0,
-                (NumberType::Float(v1), NumberType::Int(v2)) => v1 >= 1.0 || v2 >= 1,
-            },
-            start_pos: self.start_pos,
-            end_pos: self.end_pos,
-        }
-        .into_type())
-    }
 
-    fn not(mut self) -> Result<ChValue, Error> {
-        self.value = match self.value {
-            NumberType::Int(value) => if value != 0 { 0 } else { 1 }.into_number_type(),
-            NumberType::Float(value) => if value != 0.0 { 0.0 } else { 1.0 }.into_number_type(),
-        };
+    fn not(self) -> Result<ChValue, Error> {
+        let (start_pos, end_pos) = (self.start_pos, self.end_pos);
         Ok(ChBool {
-            value: self.is_true(),
-            start_pos: self.start_pos,
-            end_pos: self.end_pos,
+            value: !self.into_type().convert_to_bool(),
+            start_pos,
+            end_pos,
         }
         .into_type())
     }
 
-    fn is_true(&self) -> bool {
-        match self.value {
-            NumberType::Int(value) => value != 0,
-            NumberType::Float(value) => value != 0.0,
-        }
-    }
-
     fn negate(mut self) -> Result<ChValue, Error> {
+        self.literal = None;
         match self.value {
-            NumberType::Int(v) => self.value = NumberType::Int(-v),
+            //-ChInt::MIN does not fit into a ChInt
+            NumberType::Int(v) => {
+                self.value = match v.checked_neg() {
+                    Some(v) => NumberType::Int(v),
+                    None => NumberType::Float(-(v as ChFloat)),
+                }
+            }
             NumberType::Float(v) => self.value = NumberType::Float(-v),
         }
         Ok(self.into_type())
     }
+
+    fn identity(self) -> Result<ChValue, Error> {
+        Ok(self.into_type())
+    }
 }
 
 //--------------------------String------------------------------//
@@ -889,7 +1034,12 @@ impl Display for ChString {
 
 impl HasScope for ChString {}
 
-impl ConvertValue for ChString {}
+impl PartialEq for ChString {
+    fn eq(&self, other: &Self) -> bool {
+        self.string == other.string
+    }
+}
+
 
 impl IsChValue for ChString {
     fn into_type(self) -> ChValue {
@@ -917,45 +1067,15 @@ impl HasPosition for ChString {
 }
 
 impl ChOperators for ChString {
-    fn is_true(&self) -> bool {
-        !self.string.is_empty()
-    }
-
     fn add(mut self, other: ChValue) -> Result<ChValue, Error> {
-        let other_string = match other {
-            ChValue::Number(n) => format!("{}", n),
-            ChValue::String(s) => s.to_string(),
-            _ => {
-                return Err(Error::new(
-                    ErrType::Runtime,
-                    self.start_pos,
-                    self.end_pos,
-                    format!("can't add {:?} to String", other),
-                    None,
-                ))
-            }
-        };
-
-        self.string += &other_string;
+        self.string += &other.convert_to_string()?;
         Ok(ChValue::String(self))
     }
 
     fn mult(mut self, other: ChValue) -> Result<ChValue, Error> {
-        let n = other.into_number_type();
-
-        match n {
-            NumberType::Int(v) => {
-                self.string = self.string.repeat(v.try_into().unwrap());
-                Ok(self.into_type())
-            }
-            _ => Err(Error::new(
-                ErrType::UndefinedOperator,
-                self.start_pos,
-                self.end_pos,
-                String::from("multiply is only defined for type Int"),
-                None,
-            )),
-        }
+        let count = repeat_count(&self, &other)?;
+        self.string = self.string.repeat(count);
+        Ok(self.into_type())
     }
 
     fn access(&self, other: ChValue) -> Result<ChValue, Error> {
@@ -974,23 +1094,36 @@ impl ChOperators for ChString {
             }
         };
 
-        if num >= self.string.len().try_into().unwrap() {
+        //counts chars like len and slice, negative indices are out of bounds
+        //too, only slices count from the end
+        let c = usize::try_from(num).ok().and_then(|i| self.string.chars().nth(i));
+        let Some(c) = c else {
             return Err(Error::new(
                 ErrType::Runtime,
                 self.start_pos,
                 self.end_pos,
                 format!(
                     "Array index out of bounds => len: {}, index: {}",
-                    self.string.len(),
+                    self.string.chars().count(),
                     num
                 ),
                 None,
             ));
-        }
+        };
+
+        Ok(ChValue::String(ChString {
+            string: c.to_string(),
+            start_pos: self.start_pos,
+            end_pos: self.end_pos,
+        }))
+    }
+
+    //counts chars like len
+    fn slice(&self, start: Option<ChInt>, end: Option<ChInt>) -> Result<ChValue, Error> {
+        let (start, end) = slice_range(self.string.chars().count(), start, end);
 
-        let b: u8 = self.string.as_bytes()[num as usize];
         Ok(ChValue::String(ChString {
-            string: (b as char).to_string(),
+            string: self.string.chars().skip(start).take(end - start).collect(),
             start_pos: self.start_pos,
             end_pos: self.end_pos,
         }))
@@ -1022,7 +1155,23 @@ impl HasScope for ChFunction {
     }
 }
 
-impl ConvertValue for ChFunction {}
+impl ChFunction {
+    //a rust function called by another one (e.g. print in map) never sees a
+    //call site, it uses the settings of the calling rust function instead
+    pub fn set_settings(&mut self, settings: &Settings) {
+        if let FuncType::RustFunc(func) = &mut self.func_type {
+            func.settings = Rc::new(settings.clone());
+        }
+    }
+}
+
+
+//functions are never equal, not even to themselves
+impl PartialEq for ChFunction {
+    fn eq(&self, _other: &Self) -> bool {
+        false
+    }
+}
 
 impl HasPosition for ChFunction {
     fn get_start(&self) -> Option<Position> {
@@ -1049,7 +1198,7 @@ impl HasPosition for ChFunction {
 
 impl IsChValue for ChFunction {
     fn get_desc(&self) -> String {
-        String::from("function")
+        String::from("Function")
     }
 
     fn into_type(self) -> ChValue {
@@ -1058,9 +1207,6 @@ impl IsChValue for ChFunction {
 }
 
 impl ChOperators for ChFunction {
-    fn is_true(&self) -> bool {
-        true
-    }
 }
 
 impl IsFunction for ChFunction {
@@ -1079,19 +1225,38 @@ pub enum FuncType {
     ChronFunc(Box<ChronosFunc>),
 }
 
+pub type RustFn = fn(args: Vec<ChValue>, name: Option<String>, settings: &Settings) -> Result<ChValue, Error>;
+
 #[derive(Clone)]
 pub struct RustFunc {
     pub name: String,
-    pub function: fn(args: Vec<ChValue>, name: Option<String>) -> Result<ChValue, Error>,
+    pub function: RustFn,
+    //settings of the scope the function is called from
+    settings: Rc<Settings>,
+}
+
+impl RustFunc {
+    pub fn new(
+        name: &str,
+        function: RustFn,
+    ) -> Self {
+        RustFunc {
+            name: name.to_string(),
+            function,
+            settings: Rc::default(),
+        }
+    }
 }
 
 impl HasScope for RustFunc {
-    fn set_scope(&mut self, _scope: Rc<RefCell<Scope>>) {}
+    fn set_scope(&mut self, scope: Rc<RefCell<Scope>>) {
+        self.settings = scope.borrow().settings.clone();
+    }
 }
 
 impl IsFunction for RustFunc {
     fn execute(&mut self, args: Vec<ChValue>, name: Option<String>) -> Result<ChValue, Error> {
-        (self.function)(args, name)
+        (self.function)(args, name, &self.settings)
     }
 }
 
@@ -1110,39 +1275,143 @@ impl Debug for RustFunc {
 #[derive(Clone, Debug)]
 pub struct ChronosFunc {
     pub name: String,
-    pub args_name: Vec<Token>,
+    pub args_name: Vec<(Token, Option<Node>)>,
     pub body: Node,
     pub start_pos: Option<Position>,
     pub end_pos: Option<Position>,
+    //scope the function was defined in, closures keep it alive
     pub scope: Rc<RefCell<Scope>>,
+    pub caller: Option<Rc<RefCell<Scope>>>,
+}
+
+fn param_name(token: &Token) -> String {
+    match &token.token_type {
+        TokenType::Id(s) => s.clone(),
+        _ => String::new(),
+    }
 }
 
 impl IsFunction for ChronosFunc {
-    fn execute(&mut self, mut args: Vec<ChValue>, name: Option<String>) -> Result<ChValue, Error> {
-        let mut n_scope = Scope::from_parent(
-            format!("<function: {}>", name.unwrap_or_else(|| self.name.clone())),
+    fn execute(&mut self, args: Vec<ChValue>, name: Option<String>) -> Result<ChValue, Error> {
+        self.call(args.into_iter().map(Some).collect(), name)
+    }
+}
+
+impl ChronosFunc {
+    //the named arguments go to the parameter with the same name, after the
+    //positional ones. Parameters without an argument get their default
+    pub fn execute_named(
+        &mut self,
+        args: Vec<ChValue>,
+        named: Vec<(Token, ChValue)>,
+        name: Option<String>,
+    ) -> Result<ChValue, Error> {
+        let error = |id: &Token, details: String| {
+            Error::runtime_at(details, Some(id.start_pos), Some(id.end_pos))
+        };
+        let func_name = name.as_deref().unwrap_or(&self.name);
+
+        if args.len() > self.args_name.len() {
+            return Err(Error::runtime(format!(
+                "expected at most {} arguments, found {} in function '{}'",
+                self.args_name.len(),
+                args.len(),
+                func_name
+            )));
+        }
+
+        let mut slots: Vec<Option<ChValue>> = args.into_iter().map(Some).collect();
+        slots.resize(self.args_name.len(), None);
+
+        for (id, value) in named {
+            let arg_name = param_name(&id);
+            let index = self
+                .args_name
+                .iter()
+                .position(|(param, _)| param_name(param) == arg_name)
+                .ok_or_else(|| {
+                    error(&id, format!("function '{}' has no parameter {:?}", func_name, arg_name))
+                })?;
+
+            if slots[index].is_some() {
+                return Err(error(&id, format!("parameter {:?} got more than one value", arg_name)));
+            }
+            slots[index] = Some(value);
+        }
+
+        self.call(slots, name)
+    }
+
+    //args has an entry per given argument, None leaves a parameter to its
+    //default
+    fn call(&mut self, args: Vec<Option<ChValue>>, name: Option<String>) -> Result<ChValue, Error> {
+        let name = name.unwrap_or_else(|| self.name.clone());
+        let n_scope = Scope::from_parent(
+            format!("<function: {}>", name),
             self.scope.clone(),
             self.start_pos,
         );
+        n_scope.borrow_mut().caller = self.caller.clone();
+
+        let settings = n_scope.borrow().settings.clone();
+        let call_depth = settings.call_depth.get() + 1;
+        if call_depth > settings.max_call_depth {
+            //the call site fills in position and traceback
+            return Err(Error::runtime("maximum recursion depth exceeded"));
+        }
+
+        settings.call_depth.set(call_depth);
+        let result = self.bind_and_run(args, &name, n_scope);
+        settings.call_depth.set(call_depth - 1);
+        result
+    }
+
+    fn bind_and_run(
+        &mut self,
+        mut args: Vec<Option<ChValue>>,
+        name: &str,
+        mut n_scope: Rc<RefCell<Scope>>,
+    ) -> Result<ChValue, Error> {
+        let required = self.args_name.iter().filter(|(_, d)| d.is_none()).count();
+
+        if args.len() < required || args.len() > self.args_name.len() {
+            let expected = if required == self.args_name.len() {
+                required.to_string()
+            } else {
+                format!("{} to {}", required, self.args_name.len())
+            };
 
-        if args.len() != self.args_name.len() {
             return Err(Error::new(
                 ErrType::Runtime,
                 self.start_pos,
                 self.end_pos,
                 format!(
                     "expected {} arguments, found {} in function '{}'",
-                    self.args_name.len(),
+                    expected,
                     args.len(),
-                    self.name,
+                    name,
                 ),
                 Some(self.scope.clone()),
             ));
         }
 
-        for i in 0..args.len() {
-            let value = args.get_mut(i).unwrap();
-            let n = &self.args_name[i];
+        for i in 0..self.args_name.len() {
+            let (n, default) = &mut self.args_name[i];
+
+            //missing arguments are filled in from left to right, so defaults
+            //can use the parameters before them
+            let value = match (args.get_mut(i).and_then(Option::take), default) {
+                (Some(value), _) => value,
+                (None, Some(default)) => visit_node(default, &mut n_scope)?,
+                (None, None) => {
+                    return Err(Error::runtime(format!(
+                        "missing argument {:?} in function '{}'",
+                        param_name(n),
+                        name
+                    )))
+                }
+            };
+
             let name = match &n.token_type {
                 TokenType::Id(s) => s,
                 _ => {
@@ -1155,8 +1424,7 @@ impl IsFunction for ChronosFunc {
                     ))
                 }
             };
-            value.set_scope(self.scope.clone());
-            n_scope.borrow_mut().set_mut(name, value.clone());
+            n_scope.borrow_mut().set_mut(name, value);
         }
 
         visit_node(&mut self.body, &mut n_scope)
@@ -1165,13 +1433,15 @@ impl IsFunction for ChronosFunc {
 
 impl Display for ChronosFunc {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "function<{}, {:?}>", self.name, self.args_name)
+        let args: Vec<&Token> = self.args_name.iter().map(|(arg, _)| arg).collect();
+        write!(f, "function<{}, {:?}>", self.name, args)
     }
 }
 
+//the captured scope stays, the scope of the call site is only kept for tracebacks
 impl HasScope for ChronosFunc {
     fn set_scope(&mut self, scope: Rc<RefCell<Scope>>) {
-        self.scope = scope;
+        self.caller = Some(scope);
     }
 }
 
@@ -1192,24 +1462,108 @@ impl HasPosition for ChronosFunc {
 
 //--------------------------Array------------------------------//
 
+//arrays are shared by reference: assigning or passing an array does not copy
+//it, so push(a, 1) is visible through every variable holding a
 #[derive(Clone, Debug)]
 pub struct ChArray {
-    pub data: Vec<ChValue>,
+    pub data: Rc<RefCell<Vec<ChValue>>>,
     pub start_pos: Option<Position>,
     pub end_pos: Option<Position>,
 }
 
+impl ChArray {
+    pub fn new(data: Vec<ChValue>, start_pos: Option<Position>, end_pos: Option<Position>) -> Self {
+        ChArray {
+            data: Rc::new(RefCell::new(data)),
+            start_pos,
+            end_pos,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.borrow().is_empty()
+    }
+
+    //iter copies the elements, borrow reads them in place
+    pub fn borrow(&self) -> std::cell::Ref<'_, Vec<ChValue>> {
+        self.data.borrow()
+    }
+
+    pub fn iter(&self) -> std::vec::IntoIter<ChValue> {
+        self.data.borrow().clone().into_iter()
+    }
+}
+
+//iterates over a snapshot, changes to the array while iterating are not seen
+impl IntoIterator for ChArray {
+    type Item = ChValue;
+    type IntoIter = std::vec::IntoIter<ChValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for &ChArray {
+    type Item = ChValue;
+    type IntoIter = std::vec::IntoIter<ChValue>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+thread_local! {
+    //arrays that are currently being printed, an array containing itself prints as [...]
+    static DISPLAYING: RefCell<Vec<*const RefCell<Vec<ChValue>>>> = const { RefCell::new(Vec::new()) };
+    //pairs of arrays or maps that are currently being compared, see deep_eq
+    static COMPARING: RefCell<Vec<(*const (), *const ())>> = const { RefCell::new(Vec::new()) };
+}
+
+//structural equality of arrays and maps. A pair that is compared again
+//while its comparison is still running counts as equal, so values that
+//contain themselves compare without recursing forever
+fn deep_eq<T: PartialEq>(a: &Rc<RefCell<T>>, b: &Rc<RefCell<T>>) -> bool {
+    if Rc::ptr_eq(a, b) {
+        return true;
+    }
+
+    let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+    if COMPARING.with(|c| c.borrow().contains(&pair)) {
+        return true;
+    }
+    COMPARING.with(|c| c.borrow_mut().push(pair));
+
+    let equal = *a.borrow() == *b.borrow();
+
+    COMPARING.with(|c| c.borrow_mut().pop());
+    equal
+}
+
 impl Display for ChArray {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if self.data.is_empty() {
+        let data = self.data.borrow();
+        if data.is_empty() {
             return write!(f, "");
         }
 
-        write!(f, "[{}", self.data.get(0).unwrap())?;
-        for i in 1..self.data.len() {
-            write!(f, ", {}", self.data.get(i).unwrap())?;
+        let ptr = Rc::as_ptr(&self.data);
+        if DISPLAYING.with(|d| d.borrow().contains(&ptr)) {
+            return write!(f, "[...]");
         }
-        write!(f, "]")
+        DISPLAYING.with(|d| d.borrow_mut().push(ptr));
+
+        let mut result = write!(f, "[{}", data[0]);
+        for value in data.iter().skip(1) {
+            result = result.and_then(|_| write!(f, ", {}", value));
+        }
+
+        DISPLAYING.with(|d| d.borrow_mut().pop());
+        result.and_then(|_| write!(f, "]"))
     }
 }
 
@@ -1230,7 +1584,48 @@ impl HasPosition for ChArray {
 
 impl HasScope for ChArray {}
 
+impl PartialEq for ChArray {
+    fn eq(&self, other: &Self) -> bool {
+        deep_eq(&self.data, &other.data)
+    }
+}
+
 impl ChOperators for ChArray {
+    //a new array with the elements from start to end
+    fn slice(&self, start: Option<ChInt>, end: Option<ChInt>) -> Result<ChValue, Error> {
+        let data = self.data.borrow();
+        let (start, end) = slice_range(data.len(), start, end);
+
+        Ok(ChArray::new(data[start..end].to_vec(), self.start_pos, self.end_pos).into_type())
+    }
+
+    //element-wise, comparing with anything that is not an Array is false
+    fn equal(self, other: ChValue) -> Result<ChValue, Error> {
+        Ok(ChBool {
+            value: matches!(&other, ChValue::Array(a) if self == *a),
+            start_pos: self.start_pos,
+            end_pos: self.end_pos,
+        }
+        .into_type())
+    }
+
+    fn not_equal(self, other: ChValue) -> Result<ChValue, Error> {
+        Ok(ChBool {
+            value: !matches!(&other, ChValue::Array(a) if self == *a),
+            start_pos: self.start_pos,
+            end_pos: self.end_pos,
+        }
+        .into_type())
+    }
+
+    //a new array, the elements themselves are shared like after an assignment
+    fn mult(self, other: ChValue) -> Result<ChValue, Error> {
+        let count = repeat_count(&self, &other)?;
+        let data = self.data.borrow();
+        let data = (0..count).flat_map(|_| data.iter().cloned()).collect();
+        Ok(ChArray::new(data, self.start_pos, self.end_pos).into_type())
+    }
+
     fn access(&self, other: ChValue) -> Result<ChValue, Error> {
         let num = other.convert_to_number()?;
 
@@ -1247,25 +1642,25 @@ impl ChOperators for ChArray {
             }
         };
 
-        if num >= self.data.len().try_into().unwrap() {
+        let data = self.data.borrow();
+        if usize::try_from(num).map_or(true, |i| i >= data.len()) {
             return Err(Error::new(
                 ErrType::Runtime,
                 self.start_pos,
                 self.end_pos,
                 format!(
                     "Array index out of bounds => len: {}, index: {}",
-                    self.data.len(),
+                    data.len(),
                     num
                 ),
                 None,
             ));
         }
 
-        Ok(self.data[num as usize].clone())
+        Ok(data[num as usize].clone())
     }
 }
 
-impl ConvertValue for ChArray {}
 
 impl IsChValue for ChArray {
     fn get_desc(&self) -> String {
@@ -1276,3 +1671,129 @@ impl IsChValue for ChArray {
         ChValue::Array(self)
     }
 }
+
+//--------------------------Map------------------------------//
+
+//string keys, iterates and prints in key order.
+//shared by reference like ChArray
+#[derive(Clone, Debug)]
+pub struct ChMap {
+    pub data: Rc<RefCell<BTreeMap<String, ChValue>>>,
+    pub start_pos: Option<Position>,
+    pub end_pos: Option<Position>,
+}
+
+impl ChMap {
+    pub fn new(
+        data: BTreeMap<String, ChValue>,
+        start_pos: Option<Position>,
+        end_pos: Option<Position>,
+    ) -> Self {
+        ChMap {
+            data: Rc::new(RefCell::new(data)),
+            start_pos,
+            end_pos,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<ChValue> {
+        self.data.borrow().get(key).cloned()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.borrow().is_empty()
+    }
+
+    fn missing_key(&self, key: &str) -> Error {
+        Error::new(
+            ErrType::Runtime,
+            self.start_pos,
+            self.end_pos,
+            format!("Map has no key {:?}", key),
+            None,
+        )
+    }
+}
+
+impl Display for ChMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{")?;
+        for (i, (key, value)) in self.data.borrow().iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{:?}: {}", key, value)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+impl HasPosition for ChMap {
+    fn get_start(&self) -> Option<Position> {
+        self.start_pos
+    }
+
+    fn get_end(&self) -> Option<Position> {
+        self.end_pos
+    }
+
+    fn set_position(&mut self, start_pos: Option<Position>, end_pos: Option<Position>) {
+        self.start_pos = start_pos;
+        self.end_pos = end_pos;
+    }
+}
+
+impl HasScope for ChMap {}
+
+impl PartialEq for ChMap {
+    fn eq(&self, other: &Self) -> bool {
+        deep_eq(&self.data, &other.data)
+    }
+}
+
+impl ChOperators for ChMap {
+    fn equal(self, other: ChValue) -> Result<ChValue, Error> {
+        Ok(ChBool {
+            value: matches!(&other, ChValue::Map(m) if self == *m),
+            start_pos: self.start_pos,
+            end_pos: self.end_pos,
+        }
+        .into_type())
+    }
+
+    fn not_equal(self, other: ChValue) -> Result<ChValue, Error> {
+        Ok(ChBool {
+            value: !matches!(&other, ChValue::Map(m) if self == *m),
+            start_pos: self.start_pos,
+            end_pos: self.end_pos,
+        }
+        .into_type())
+    }
+
+    fn access(&self, other: ChValue) -> Result<ChValue, Error> {
+        let key = match &other {
+            ChValue::String(s) => &s.string,
+            _ => {
+                return Err(Error::new(
+                    ErrType::Runtime,
+                    other.get_start(),
+                    other.get_end(),
+                    format!("expected String found: {}", other.type_name()),
+                    None,
+                ))
+            }
+        };
+
+        self.get(key).ok_or_else(|| self.missing_key(key))
+    }
+}
+
+impl IsChValue for ChMap {
+    fn get_desc(&self) -> String {
+        String::from("Map")
+    }
+
+    fn into_type(self) -> ChValue {
+        ChValue::Map(self)
+    }
+}
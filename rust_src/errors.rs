@@ -1,6 +1,10 @@
-use std::{cell::RefCell, fmt::Write, rc::Rc};
+use std::{
+    cell::RefCell,
+    fmt::{self, Write},
+    rc::Rc,
+};
 
-use crate::chronos::{Position, Scope, File};
+use crate::chronos::{ChInt, File, Position, Scope};
 
 //#[derive(Debug)]
 //pub struct ErrDesc {
@@ -8,6 +12,14 @@ use crate::chronos::{Position, Scope, File};
 //    details: String,
 //}
 
+//control flow that unwinds through visit_node like an error
+#[derive(Debug, Clone, Copy)]
+pub enum Signal {
+    Exit(ChInt),
+    //caught by the innermost loop
+    Break,
+}
+
 #[derive(Debug)]
 pub enum ErrType {
     IllegalChar,
@@ -15,6 +27,7 @@ pub enum ErrType {
     InvalidSyntax,
     Runtime,
     UndefinedOperator,
+    Signal(Signal),
 }
 
 pub struct Error {
@@ -44,14 +57,47 @@ impl Error {
         }
     }
 
+    //for host functions that have no positions at hand, visit_call_node
+    //points positionless errors at the call site
+    pub fn runtime(details: impl Into<String>) -> Self {
+        Error::new(ErrType::Runtime, None, None, details.into(), None)
+    }
+
+    pub fn runtime_at(details: impl Into<String>, start_pos: Option<Position>, end_pos: Option<Position>) -> Self {
+        Error::new(ErrType::Runtime, start_pos, end_pos, details.into(), None)
+    }
+
     pub fn print(&self) {
-        if self.files.is_some() && self.start_pos.is_some() && self.end_pos.is_some() {
-            println!("{}", self.generate_message(self.files.as_ref().unwrap()));
-        } else {
-            println!("{:?}: {}", self.error_type, self.details);
+        println!("{}", self);
+    }
+
+    //for tools that map errors to their own diagnostics, None if the error
+    //never got a position
+    pub fn span(&self) -> Option<(Position, Position)> {
+        self.start_pos.zip(self.end_pos)
+    }
+
+    pub fn message(&self) -> &str {
+        &self.details
+    }
+
+    pub fn kind(&self) -> &ErrType {
+        &self.error_type
+    }
+
+    pub fn signal(&self) -> Option<Signal> {
+        match self.error_type {
+            ErrType::Signal(s) => Some(s),
+            _ => None,
         }
     }
 
+    //errors of a running program, try/catch can handle these. Syntax errors
+    //and signals like exit always reach the host
+    pub fn is_catchable(&self) -> bool {
+        matches!(self.error_type, ErrType::Runtime | ErrType::UndefinedOperator)
+    }
+
     pub fn set_files(&mut self, files: Vec<File>) {
         self.files = Some(files);
     }
@@ -81,13 +127,78 @@ impl Error {
     pub fn set_scope(&mut self, scope: Rc<RefCell<Scope>>) {
         self.scope = Some(scope);
     }
+
+    pub fn has_scope(&self) -> bool {
+        self.scope.is_some()
+    }
+
+    pub fn has_position(&self) -> bool {
+        self.start_pos.is_some() && self.end_pos.is_some()
+    }
+
+    pub fn set_position(&mut self, start_pos: Position, end_pos: Position) {
+        self.start_pos = Some(start_pos);
+        self.end_pos = Some(end_pos);
+    }
 }
 
-//impl fmt::Display for Error {
-//    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-//        write!(f, "{}", self.generate_message(),)
-//    }
-//}
+//reported by the checks in lint.rs, the code still gets interpreted
+pub struct Warning {
+    start_pos: Position,
+    end_pos: Position,
+    details: String,
+}
+
+impl Warning {
+    pub fn new(start_pos: Position, end_pos: Position, details: String) -> Self {
+        Warning {
+            start_pos,
+            end_pos,
+            details,
+        }
+    }
+
+    pub fn print(&self, files: &[File]) {
+        println!("{}", self.generate_message(files));
+    }
+
+    pub fn generate_message(&self, files: &[File]) -> String {
+        let file_name = &files.get(self.start_pos.file_nr).unwrap().name;
+
+        format!(
+            "Warning: {}\n\n{}\nFile: {}, Line: {}",
+            self.details,
+            get_error_preview(self.start_pos.file_nr, files, &self.start_pos, &self.end_pos),
+            file_name,
+            self.start_pos.line
+        )
+    }
+}
+
+//the full message with traceback and preview once set_files was called,
+//only the type and details before that
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.files, self.span()) {
+            (Some(files), Some(_)) => write!(f, "{}", self.generate_message(files)),
+            _ => write!(f, "{:?}: {}", self.error_type, self.details),
+        }
+    }
+}
+
+//the scope is left out, it would print every variable of the traceback
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Error")
+            .field("error_type", &self.error_type)
+            .field("start_pos", &self.start_pos)
+            .field("end_pos", &self.end_pos)
+            .field("details", &self.details)
+            .finish()
+    }
+}
+
+impl std::error::Error for Error {}
 
 fn get_traceback(scope: &Option<Rc<RefCell<Scope>>>, pos_start: &Position, files: &[File]) -> String {
     let mut result = String::from("");
@@ -105,7 +216,6 @@ fn get_traceback(scope: &Option<Rc<RefCell<Scope>>>, pos_start: &Position, files
             write!(
                 s,
                 "\n  File: {}, Line: {}, in {}",
-                //pos.file_name,
                 files.get(pos.file_nr).unwrap().name, 
                 pos.line,
                 cntx.borrow().display_name
@@ -116,7 +226,8 @@ fn get_traceback(scope: &Option<Rc<RefCell<Scope>>>, pos_start: &Position, files
 
             let parent: Rc<RefCell<Scope>>;
 
-            if let Some(p) = cntx.borrow().parent.clone() {
+            let next = cntx.borrow().caller.clone().or_else(|| cntx.borrow().parent.clone());
+            if let Some(p) = next {
                 pos = cntx.borrow().position.unwrap_or_default();
                 parent = p;
             } else {
@@ -126,45 +237,73 @@ fn get_traceback(scope: &Option<Rc<RefCell<Scope>>>, pos_start: &Position, files
             cntx = parent;
         }
 
-        for t in trace.iter().rev() {
+        //deep recursion repeats the same frame, only the first few are printed
+        let mut repeated = 0;
+        for (i, t) in trace.iter().rev().enumerate() {
+            if i > 0 && trace[trace.len() - i] == *t {
+                repeated += 1;
+                if repeated >= 3 {
+                    continue;
+                }
+            } else {
+                write_repeated(&mut result, repeated);
+                repeated = 0;
+            }
             write!(result, "{}", t).unwrap();
         }
+        write_repeated(&mut result, repeated);
     }
 
     writeln!(result).unwrap();
     result
 }
 
+fn write_repeated(result: &mut String, repeated: usize) {
+    if repeated >= 3 {
+        write!(result, "\n  [Previous line repeated {} more times]", repeated - 2).unwrap();
+    }
+}
+
+//underlines the span in its source lines, the span may reach past the end
+//of the text (errors at EOF) or cover multiple lines
 fn get_error_preview(file_nr: usize, files: &[File], pos_start: &Position, pos_end: &Position) -> String {
     let mut result = String::from("");
     let file = files.get(file_nr).unwrap();
     let text = &file.text;
 
-    let mut start = text[0..pos_start.offset + pos_start.index].rfind('\n').unwrap_or(0);
-    let mut end = text[start + 1..].find('\n').unwrap_or(text.len() - start + 1) + start + 1;
+    let mut index = (pos_start.offset + pos_start.index).min(text.len());
+    let mut col_start = pos_start.column;
+    let mut col_end = pos_end.column;
+
+    //EOF after a trailing newline is shown at the end of the last line
+    if index == text.len() && text.ends_with('\n') {
+        index -= 1;
+        col_start = text[..index].rfind('\n').map_or(index, |i| index - i - 1);
+        col_end = col_start + 1;
+    }
 
-    let count = pos_end.line - pos_start.line + 1;
+    let line_start = text.as_bytes()[..index]
+        .iter()
+        .rposition(|b| *b == b'\n')
+        .map_or(0, |i| i + 1);
 
-    for i in 0..count {
-        let line = &text[start..end];
-        let col_start = if i == 0 { pos_start.column } else { 0 };
-        let col_end = if i == count - 1 {
-            pos_end.column
-        } else {
-            line.len() - 1
-        };
+    let count = pos_end.line.saturating_sub(pos_start.line) + 1;
 
+    for (i, line) in text[line_start..].split('\n').take(count).enumerate() {
+        let col_start = if i == 0 { col_start } else { 0 };
+        let col_end = if i == count - 1 { col_end } else { line.len() };
+
+        if i > 0 {
+            result.push('\n');
+        }
         write!(
             result,
             "{}\n{}{}",
-            line.replace("\n", ""),
+            line,
             " ".repeat(col_start),
-            "~".repeat(col_end - col_start)
+            "~".repeat(col_end.saturating_sub(col_start))
         )
         .unwrap();
-
-        start = end;
-        end = text[0..start].find('\n').unwrap_or_else(|| text.len());
     }
 
     result.replace("\t", "")
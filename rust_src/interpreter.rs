@@ -1,28 +1,105 @@
 use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
 use std::rc::Rc;
 
 use crate::chronos::*;
 use crate::datatypes::*;
 use crate::errors::*;
+use crate::parser::Parser;
 
 pub fn visit_node(node: &mut Node, scope: &mut Rc<RefCell<Scope>>) -> Result<ChValue, Error> {
+    count_step(node, scope)?;
+
     use Node::*;
     match node {
         Num(token) => visit_numb_node(token, scope),
         String(token) => visit_string_node(token, scope),
         Array(array, start, end) => visit_array_node(array, start, end, scope),
+        Map(entries, start, end) => visit_map_node(entries, start, end, scope),
         UnryOp(op, node) => visit_unryop_node(op, node, scope),
+        PrefixOp(op, node) => visit_step_node(op, node, true, scope),
+        PostfixOp(node, op) => visit_step_node(op, node, false, scope),
         BinOp(left, op, right) => visit_binop_node(left, op, right, scope),
         Access(id) => visit_access_node(id, scope),
         Assign(id, value) => visit_assign_node(id, value, scope),
+        MultiAssign(ids, value) => visit_multiassign_node(ids, value, scope),
+        Let(id, value) => visit_let_node(id, value, scope),
+        Const(id, value) => visit_const_node(id, value, scope),
         If(cases, else_case) => visit_if_node(cases, else_case, scope),
-        While(cond, body, start, end) => visit_while_node(cond, body, scope, start, end),
-        For(c1, c2, c3, body, start, end) => visit_for_node(c1, c2, c3, body, scope, start, end),
+        While(cond, body, else_case, start, end) => {
+            visit_while_node(cond, body, else_case, scope, start, end)
+        }
+        DoWhile(body, cond, else_case, start, end) => {
+            visit_dowhile_node(body, cond, else_case, scope, start, end)
+        }
+        Try(body, id, handler, ..) => visit_try_node(body, id, handler, scope),
+        For(c1, c2, c3, body, else_case, start, end) => {
+            visit_for_node(c1, c2, c3, body, else_case, scope, start, end)
+        }
+        ForEach(var, collection, body, else_case, start, end) => {
+            visit_foreach_node(var, collection, body, else_case, scope, start, end)
+        }
         FuncDef(name, args, body, start, end) => {
             visit_funcdef_node(name, args, body, start, end, scope)
         }
         Call(name, args) => visit_call_node(name, args, scope),
+        NamedArg(id, _) => Err(Error::runtime_at(
+            "named argument outside of a call",
+            Some(id.start_pos),
+            Some(id.end_pos),
+        )),
         ArrAccess(name, indx) => visit_arraccess_node(name, indx, scope),
+        Slice(value, start, end) => visit_slice_node(value, start, end, scope),
+        Member(node, member) => visit_member_node(node, member, scope),
+        Ternary(cond, then_expr, else_expr) => visit_ternary_node(cond, then_expr, else_expr, scope),
+        Del(id) => visit_del_node(id, scope),
+        Import(path) => visit_import_node(path, scope),
+        Throw(value, start, end) => visit_throw_node(value, start, end, scope),
+        Break(token) => visit_break_node(token, scope),
+        Block(nodes, start, end) => visit_block_node(nodes, start, end, scope),
+    }
+}
+
+//every visited node is one step, only counted when a step limit is set
+fn count_step(node: &Node, scope: &Rc<RefCell<Scope>>) -> Result<(), Error> {
+    let scope_ref = scope.borrow();
+    let settings = &scope_ref.settings;
+
+    if let Some(limit) = settings.step_limit {
+        let steps = settings.steps.get() + 1;
+        settings.steps.set(steps);
+
+        if steps > limit {
+            let (start, end) = node.span();
+            return Err(Error::new(
+                ErrType::Runtime,
+                Some(start),
+                Some(end),
+                String::from("execution limit exceeded"),
+                Some(scope.clone()),
+            ));
+        }
+    }
+    Ok(())
+}
+
+//the parser never builds nodes with these tokens, a hand built AST (or a bug)
+//gets an error instead of a panic
+fn unexpected_token(token: &Token, expected: &str) -> Error {
+    Error::runtime_at(
+        format!("expected {} found {:?}", expected, token),
+        Some(token.start_pos),
+        Some(token.end_pos),
+    )
+}
+
+fn expect_id(token: &Token) -> Result<&String, Error> {
+    match &token.token_type {
+        TokenType::Id(name) => Ok(name),
+        _ => Err(unexpected_token(token, "ID")),
     }
 }
 
@@ -32,13 +109,15 @@ fn visit_numb_node(token: &mut Token, _scope: &mut Rc<RefCell<Scope>>) -> Result
             value: value.into_number_type(),
             start_pos: Some(token.start_pos),
             end_pos: Some(token.end_pos),
+            literal: token.literal.clone(),
         })),
         TokenType::Float(value) => Ok(ChValue::Number(ChNumber {
             value: value.into_number_type(),
             start_pos: Some(token.start_pos),
             end_pos: Some(token.end_pos),
+            literal: token.literal.clone(),
         })),
-        _ => panic!("called visit_numb_node on a number node that has a non number token"),
+        _ => Err(unexpected_token(token, "INT or FLOAT")),
     }
 }
 
@@ -49,33 +128,69 @@ fn visit_string_node(token: &mut Token, _scope: &mut Rc<RefCell<Scope>>) -> Resu
             start_pos: Some(token.start_pos),
             end_pos: Some(token.end_pos),
         })),
-        _ => panic!("called visit_string_node on a string node that has a non string token"),
+        _ => Err(unexpected_token(token, "STRING")),
     }
 }
 
 fn visit_access_node(token: &mut Token, scope: &mut Rc<RefCell<Scope>>) -> Result<ChValue, Error> {
-    let var = &token.token_type;
-    match var {
-        TokenType::Id(var_name) => {
-            let mut entry = scope.borrow().get(var_name);
-
-            match &mut entry {
-                Some(num) => {
-                    num.set_position(Some(token.start_pos), Some(token.end_pos));
-                    num.set_scope(scope.clone());
-                    Ok(num.clone())
-                }
-                None => Err(Error::new(
-                    ErrType::Runtime,
-                    Some(token.start_pos),
-                    Some(token.end_pos),
-                    format!("{:?} is not defined", var_name),
-                    Some(scope.clone()),
-                )),
-            }
+    let var_name = expect_id(token)?;
+    let mut entry = scope.borrow().get(var_name);
+
+    match &mut entry {
+        Some(num) => {
+            num.set_position(Some(token.start_pos), Some(token.end_pos));
+            Ok(num.clone())
         }
-        _ => panic!("called visit_access_node on a non ID token"),
+        None => {
+            let details = match scope.borrow().closest_name(var_name) {
+                Some(name) => format!("{:?} is not defined, did you mean {:?}?", var_name, name),
+                None => format!("{:?} is not defined", var_name),
+            };
+
+            Err(Error::new(
+                ErrType::Runtime,
+                Some(token.start_pos),
+                Some(token.end_pos),
+                details,
+                Some(scope.clone()),
+            ))
+        }
+    }
+}
+
+//errors if assign_var would fail, so that several variables can be checked
+//before any of them is assigned
+fn check_assign(id: &Token, value: &ChValue, scope: &Rc<RefCell<Scope>>) -> Result<(), Error> {
+    let var_name = expect_id(id)?;
+
+    let (start, end, details) = match scope.borrow().can_assign(var_name) {
+        Some(true) => return Ok(()),
+        Some(false) => (
+            value.get_start(),
+            value.get_end(),
+            format!("cannot assign {} to const {:?}", value, var_name),
+        ),
+        None if !scope.borrow().settings.strict => return Ok(()),
+        None => (
+            Some(id.start_pos),
+            Some(id.end_pos),
+            format!("'{}' not declared; use 'let {} = ...'", var_name, var_name),
+        ),
+    };
+
+    Err(Error::new(ErrType::Runtime, start, end, details, Some(scope.clone())))
+}
+
+//stores value in an existing variable, which may live in a parent scope,
+//unknown variables are declared in the current scope unless in strict mode
+fn assign_var(id: &Token, value: ChValue, scope: &mut Rc<RefCell<Scope>>) -> Result<(), Error> {
+    check_assign(id, &value, scope)?;
+    let var_name = expect_id(id)?;
+
+    if scope.borrow_mut().assign(var_name, value.clone()).is_none() {
+        scope.borrow_mut().set_mut(var_name, value);
     }
+    Ok(())
 }
 
 fn visit_assign_node(
@@ -83,31 +198,210 @@ fn visit_assign_node(
     value: &mut Node,
     scope: &mut Rc<RefCell<Scope>>,
 ) -> Result<ChValue, Error> {
-    let t = id.clone();
     let ch_type = visit_node(value, scope)?;
+    assign_var(id, ch_type.clone(), scope)?;
+    Ok(ch_type)
+}
 
-    match t.token_type {
-        TokenType::Id(var_name) => {
-            if !scope.borrow_mut().set_mut(&var_name, ch_type.clone()) {
-                return Err(Error::new(
-                    ErrType::Runtime,
-                    ch_type.get_start(),
-                    ch_type.get_end(),
-                    format!("cannot assign {} to const {:?}", ch_type, var_name),
-                    Some(scope.clone()),
-                ));
-            }
-            Ok(ch_type)
+fn visit_multiassign_node(
+    ids: &mut [Token],
+    value: &mut Node,
+    scope: &mut Rc<RefCell<Scope>>,
+) -> Result<ChValue, Error> {
+    let ch_type = visit_node(value, scope)?;
+
+    let values = match &ch_type {
+        ChValue::Array(a) => a.data.borrow().clone(),
+        other => {
+            return Err(Error::new(
+                ErrType::Runtime,
+                other.get_start(),
+                other.get_end(),
+                format!("cannot unpack {} into {} variables", other.type_name(), ids.len()),
+                Some(scope.clone()),
+            ))
+        }
+    };
+
+    if values.len() != ids.len() {
+        return Err(Error::new(
+            ErrType::Runtime,
+            ch_type.get_start(),
+            ch_type.get_end(),
+            format!("expected {} values to unpack, found {}", ids.len(), values.len()),
+            Some(scope.clone()),
+        ));
+    }
+
+    //either all of the variables are assigned or none
+    for (id, v) in ids.iter().zip(&values) {
+        check_assign(id, v, scope)?;
+    }
+    for (id, v) in ids.iter().zip(values) {
+        assign_var(id, v, scope)?;
+    }
+    Ok(ch_type)
+}
+
+//declares the variable in the current scope, shadowing outer ones
+fn visit_let_node(
+    id: &mut Token,
+    value: &mut Node,
+    scope: &mut Rc<RefCell<Scope>>,
+) -> Result<ChValue, Error> {
+    let var_name = expect_id(id)?;
+    let ch_type = visit_node(value, scope)?;
+
+    if !scope.borrow_mut().set_mut(var_name, ch_type.clone()) {
+        return Err(Error::new(
+            ErrType::Runtime,
+            ch_type.get_start(),
+            ch_type.get_end(),
+            format!("cannot assign {} to const {:?}", ch_type, var_name),
+            Some(scope.clone()),
+        ));
+    }
+    Ok(ch_type)
+}
+
+//like let, but the variable can not be reassigned or deleted afterwards
+fn visit_const_node(
+    id: &mut Token,
+    value: &mut Node,
+    scope: &mut Rc<RefCell<Scope>>,
+) -> Result<ChValue, Error> {
+    let var_name = expect_id(id)?;
+    let ch_type = visit_node(value, scope)?;
+
+    if !scope.borrow_mut().set(var_name, ch_type.clone()) {
+        return Err(Error::new(
+            ErrType::Runtime,
+            ch_type.get_start(),
+            ch_type.get_end(),
+            format!("cannot assign {} to const {:?}", ch_type, var_name),
+            Some(scope.clone()),
+        ));
+    }
+    Ok(ch_type)
+}
+
+fn visit_del_node(id: &mut Token, scope: &mut Rc<RefCell<Scope>>) -> Result<ChValue, Error> {
+    let var_name = expect_id(id)?;
+
+    let details = match scope.borrow_mut().remove(var_name) {
+        Some(true) => {
+            return Ok(ChValue::None(ChNone {
+                start_pos: Some(id.start_pos),
+                end_pos: Some(id.end_pos),
+            }))
+        }
+        Some(false) => format!("cannot delete const {:?}", var_name),
+        None => format!("{:?} is not defined", var_name),
+    };
+
+    Err(Error::new(
+        ErrType::Runtime,
+        Some(id.start_pos),
+        Some(id.end_pos),
+        details,
+        Some(scope.clone()),
+    ))
+}
+
+//interprets the file in the global scope, so its definitions become globals.
+//A file that was imported before is skipped, one that is still being
+//imported further up is a circular import
+fn visit_import_node(path: &mut Token, scope: &mut Rc<RefCell<Scope>>) -> Result<ChValue, Error> {
+    let name = match &path.token_type {
+        TokenType::String(s) => s,
+        _ => return Err(unexpected_token(path, "STRING")),
+    };
+    let error = |details: String| {
+        Error::new(
+            ErrType::Runtime,
+            Some(path.start_pos),
+            Some(path.end_pos),
+            details,
+            Some(scope.clone()),
+        )
+    };
+
+    let settings = scope.borrow().settings.clone();
+
+    //names like <stdin> are not paths, their imports are relative to the
+    //working directory
+    let importer = settings.files.borrow().files[path.start_pos.file_nr].name.clone();
+    let file_path = match Path::new(&importer).parent() {
+        Some(dir) if !importer.starts_with('<') => dir.join(name),
+        _ => Path::new(name).to_path_buf(),
+    };
+
+    let text = fs::read_to_string(&file_path)
+        .map_err(|e| error(format!("cannot import {:?}: {}", name, e)))?;
+    let key = fs::canonicalize(&file_path).unwrap_or_else(|_| file_path.clone());
+
+    let none = ChValue::None(ChNone {
+        start_pos: Some(path.start_pos),
+        end_pos: Some(path.end_pos),
+    });
+
+    let file_nr = {
+        let mut fm = settings.files.borrow_mut();
+        if fm.imported.contains(&key) {
+            return Ok(none);
+        }
+        if fm.importing.contains(&key) {
+            return Err(error(format!("circular import of {:?}", name)));
+        }
+        fm.importing.push(key.clone());
+        fm.add_import(file_path.display().to_string(), text.clone())
+    };
+
+    let mut global = scope.clone();
+    loop {
+        let parent = global.borrow().parent.clone();
+        match parent {
+            Some(p) => global = p,
+            None => break,
         }
-        _ => panic!("called visit_assign_node on {:?}", value),
     }
+
+    let result = lex(&settings, file_nr, 0, 0, text)
+        .and_then(|tokens| Parser::new(tokens).parse_file())
+        .and_then(|mut ast| visit_node(&mut ast, &mut global));
+
+    let mut fm = settings.files.borrow_mut();
+    fm.importing.retain(|p| *p != key);
+    if result.is_ok() {
+        fm.imported.insert(key);
+    }
+
+    result.map(|_| none)
+}
+
+fn visit_throw_node(
+    value: &mut Node,
+    start: &Position,
+    end: &Position,
+    scope: &mut Rc<RefCell<Scope>>,
+) -> Result<ChValue, Error> {
+    let value = visit_node(value, scope)?;
+
+    Err(Error::new(
+        ErrType::Runtime,
+        Some(*start),
+        Some(*end),
+        value.to_string(),
+        Some(scope.clone()),
+    ))
 }
 
 fn unryop_chvalue<T: IsChValue>(op_token: &Token, value: T) -> Result<ChValue, Error> {
     match op_token.token_type {
         TokenType::Sub => value.negate(),
+        TokenType::Add => value.identity(),
         TokenType::Keywrd(Keyword::Not) => value.not(),
-        _ => panic!("called unryop_self on {:?}", op_token),
+        _ => Err(unexpected_token(op_token, "'-', '+' or '!'")),
     }
 }
 
@@ -122,6 +416,62 @@ fn visit_unryop_node(
     unryop_chvalue(op, ch_value)
 }
 
+//++ and --, evaluates to the new value as prefix and to the old one as postfix
+fn visit_step_node(
+    op: &mut Token,
+    target: &mut Node,
+    prefix: bool,
+    scope: &mut Rc<RefCell<Scope>>,
+) -> Result<ChValue, Error> {
+    let id = match target {
+        Node::Access(id) => id.clone(),
+        _ => {
+            let (start, end) = target.span();
+            return Err(Error::new(
+                ErrType::Runtime,
+                Some(start),
+                Some(end),
+                format!("expected LVALUE, found {:?}", target),
+                Some(scope.clone()),
+            ))
+        }
+    };
+
+    let old = visit_node(target, scope)?;
+
+    let number = match &old {
+        ChValue::Number(n) => n.clone(),
+        _ => {
+            return Err(Error::new(
+                ErrType::UndefinedOperator,
+                old.get_start(),
+                old.get_end(),
+                format!(
+                    "operator '{}' not defined for type: {}",
+                    if matches!(op.token_type, TokenType::PlusPlus) { "++" } else { "--" },
+                    old.type_name()
+                ),
+                Some(scope.clone()),
+            ))
+        }
+    };
+
+    let one = ChValue::Number(ChNumber {
+        value: 1.into_number_type(),
+        start_pos: Some(op.start_pos),
+        end_pos: Some(op.end_pos),
+        literal: None,
+    });
+
+    let new = match op.token_type {
+        TokenType::PlusPlus => number.add(one)?,
+        _ => number.sub(one)?,
+    };
+
+    assign_var(&id, new.clone(), scope)?;
+    Ok(if prefix { new } else { old })
+}
+
 fn binop_chvalue<T: IsChValue>(
     left: T,
     op_token: &Token,
@@ -133,6 +483,7 @@ fn binop_chvalue<T: IsChValue>(
         Sub => left.sub(right),
         Mul => left.mult(right),
         Div => left.div(right),
+        Mod => left.modulo(right),
         Pow => left.pow(right),
         Less => left.less(right),
         Equal => left.equal(right),
@@ -140,9 +491,58 @@ fn binop_chvalue<T: IsChValue>(
         LessEq => left.less_equal(right),
         Greater => left.greater(right),
         GreaterEq => left.greater_equal(right),
-        Keywrd(Keyword::And) => left.and(right),
-        Keywrd(Keyword::Or) => left.or(right),
-        _ => panic!("called binop_bool on {:?}", op_token.token_type),
+        _ => Err(unexpected_token(op_token, "a binary operator")),
+    }
+}
+
+//fast path for the common Int op Int case, None means the general path
+//has to handle it (other types, errors or overflow)
+fn int_binop(left: &ChValue, op: &Token, right: &ChValue) -> Option<ChValue> {
+    use TokenType::*;
+
+    let (l, r) = match (left, right) {
+        (
+            ChValue::Number(ChNumber {
+                value: NumberType::Int(l),
+                ..
+            }),
+            ChValue::Number(ChNumber {
+                value: NumberType::Int(r),
+                ..
+            }),
+        ) => (*l, *r),
+        _ => return None,
+    };
+
+    let number = |v: Option<ChInt>| {
+        v.map(|v| {
+            ChValue::Number(ChNumber {
+                value: NumberType::Int(v),
+                start_pos: left.get_start(),
+                end_pos: right.get_end(),
+                literal: None,
+            })
+        })
+    };
+    let boolean = |v: bool| {
+        Some(ChValue::Bool(ChBool {
+            value: v,
+            start_pos: left.get_start(),
+            end_pos: right.get_end(),
+        }))
+    };
+
+    match op.token_type {
+        Add => number(l.checked_add(r)),
+        Sub => number(l.checked_sub(r)),
+        Mul => number(l.checked_mul(r)),
+        Less => boolean(l < r),
+        LessEq => boolean(l <= r),
+        Greater => boolean(l > r),
+        GreaterEq => boolean(l >= r),
+        Equal => boolean(l == r),
+        NEqual => boolean(l != r),
+        _ => None,
     }
 }
 
@@ -152,12 +552,32 @@ fn visit_binop_node(
     right: &mut Node,
     scope: &mut Rc<RefCell<Scope>>,
 ) -> Result<ChValue, Error> {
-    if matches!(op.token_type, TokenType::AddEq) || matches!(op.token_type, TokenType::SubEq) {
-        return add_sub_equal(left, op, right, scope);
+    if matches!(
+        op.token_type,
+        TokenType::AddEq
+            | TokenType::SubEq
+            | TokenType::MulEq
+            | TokenType::DivEq
+            | TokenType::ModEq
+    ) {
+        return compound_assign(left, op, right, scope);
+    }
+    if matches!(
+        op.token_type,
+        TokenType::Keywrd(Keyword::And) | TokenType::Keywrd(Keyword::Or)
+    ) {
+        return and_or(left, op, right, scope);
+    }
+    if matches!(op.token_type, TokenType::Coalesce) {
+        return coalesce(left, right, scope);
     }
     let mut left = visit_node(left, scope)?;
     let right = visit_node(right, scope)?;
 
+    if let Some(value) = int_binop(&left, op, &right) {
+        return Ok(value);
+    }
+
     left.set_position(left.get_start(), right.get_end());
 
     let ret = binop_chvalue(left, op, right);
@@ -169,7 +589,41 @@ fn visit_binop_node(
     }
 }
 
-fn add_sub_equal(
+//returns the operand that decided the result, the right side is only
+//evaluated when the left one does not decide it already
+fn and_or(
+    left_node: &mut Node,
+    op: &mut Token,
+    right_node: &mut Node,
+    scope: &mut Rc<RefCell<Scope>>,
+) -> Result<ChValue, Error> {
+    let left = visit_node(left_node, scope)?;
+
+    let decided = match op.token_type {
+        TokenType::Keywrd(Keyword::And) => !left.is_true(),
+        _ => left.is_true(),
+    };
+
+    if decided {
+        Ok(left)
+    } else {
+        visit_node(right_node, scope)
+    }
+}
+
+//'a ?? b' is b only if a is none, b is not evaluated otherwise
+fn coalesce(
+    left_node: &mut Node,
+    right_node: &mut Node,
+    scope: &mut Rc<RefCell<Scope>>,
+) -> Result<ChValue, Error> {
+    match visit_node(left_node, scope)? {
+        ChValue::None(_) => visit_node(right_node, scope),
+        left => Ok(left),
+    }
+}
+
+fn compound_assign(
     left_node: &mut Node,
     op: &mut Token,
     right_node: &mut Node,
@@ -177,8 +631,6 @@ fn add_sub_equal(
 ) -> Result<ChValue, Error> {
     let mut left = visit_node(left_node, scope)?;
     let right = visit_node(right_node, scope)?;
-    let start = left.get_start();
-    let end = left.get_end();
 
     match left_node {
         Node::Access(var_name) => {
@@ -187,21 +639,19 @@ fn add_sub_equal(
             let res = match op.token_type {
                 TokenType::AddEq => left.add_equal(right)?,
                 TokenType::SubEq => left.sub_equal(right)?,
-                _ => panic!("called add/sub_equal on {:?}", op),
-            };
-
-            let name = match &var_name.token_type {
-                TokenType::Id(n) => n,
-                _ => panic!("could not resolve name"),
+                TokenType::MulEq => left.mult(right)?,
+                TokenType::DivEq => left.div(right)?,
+                TokenType::ModEq => left.modulo(right)?,
+                _ => return Err(unexpected_token(op, "a compound assignment")),
             };
 
-            scope.borrow_mut().set_mut(name, res.clone());
+            assign_var(var_name, res.clone(), scope)?;
             Ok(res)
         }
         _ => Err(Error::new(
             ErrType::Runtime,
-            start,
-            end,
+            Some(left_node.span().0),
+            Some(left_node.span().1),
             format!("expected LVALUE, found {:?}", left_node),
             Some(scope.clone()),
         )),
@@ -227,12 +677,16 @@ fn visit_if_node(
         }
 
         if cond.is_true() {
-            return visit_node(expr, scope);
+            let mut n_scope = Scope::from_parent(String::from("<if>"), scope.clone(), cond.get_start());
+            return visit_node(expr, &mut n_scope);
         }
     }
 
     match else_case {
-        Some(node) => visit_node(node, scope),
+        Some(node) => {
+            let mut n_scope = Scope::from_parent(String::from("<else>"), scope.clone(), start);
+            visit_node(node, &mut n_scope)
+        }
         _ => Ok(ChValue::None(ChNone {
             start_pos: start,
             end_pos: end,
@@ -240,56 +694,258 @@ fn visit_if_node(
     }
 }
 
+fn visit_block_node(
+    nodes: &mut [Node],
+    start: &Position,
+    end: &Position,
+    scope: &mut Rc<RefCell<Scope>>,
+) -> Result<ChValue, Error> {
+    let mut value = ChValue::None(ChNone {
+        start_pos: Some(*start),
+        end_pos: Some(*end),
+    });
+
+    for node in nodes {
+        value = visit_node(node, scope)?;
+    }
+    Ok(value)
+}
+
+//a loop evaluates to the values of its completed iterations,
+//or none if the body never ran. break stops the accumulation: the values
+//collected so far are returned and the iteration it leaves adds none.
+//The else block only runs for its side effects and adds none either
+fn loop_result(values: Vec<ChValue>, start: &Position, end: &Position) -> ChValue {
+    if values.is_empty() {
+        ChValue::None(ChNone {
+            start_pos: Some(*start),
+            end_pos: Some(*end),
+        })
+    } else {
+        ChValue::Array(ChArray::new(values, Some(*start), Some(*end)))
+    }
+}
+
+//None if the iteration was left with break
+fn visit_loop_body(body: &mut Node, scope: &mut Rc<RefCell<Scope>>) -> Result<Option<ChValue>, Error> {
+    match visit_node(body, scope) {
+        Err(e) if matches!(e.signal(), Some(Signal::Break)) => Ok(None),
+        result => result.map(Some),
+    }
+}
+
+//runs once the loop finished without break, in the loop's scope
+fn visit_loop_else(else_case: &mut Option<Box<Node>>, scope: &mut Rc<RefCell<Scope>>) -> Result<(), Error> {
+    if let Some(e) = else_case {
+        visit_node(e, scope)?;
+    }
+    Ok(())
+}
+
+fn visit_break_node(token: &mut Token, scope: &mut Rc<RefCell<Scope>>) -> Result<ChValue, Error> {
+    Err(Error::new(
+        ErrType::Signal(Signal::Break),
+        Some(token.start_pos),
+        Some(token.end_pos),
+        String::from("'break' outside of a loop"),
+        Some(scope.clone()),
+    ))
+}
+
+fn visit_ternary_node(
+    condition: &mut Node,
+    then_expr: &mut Node,
+    else_expr: &mut Node,
+    scope: &mut Rc<RefCell<Scope>>,
+) -> Result<ChValue, Error> {
+    if visit_node(condition, scope)?.is_true() {
+        visit_node(then_expr, scope)
+    } else {
+        visit_node(else_expr, scope)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
 fn visit_for_node(
     c1: &mut Option<Box<Node>>,
-    c2: &mut Box<Node>,
+    c2: &mut Option<Box<Node>>,
     c3: &mut Option<Box<Node>>,
     body: &mut Node,
+    else_case: &mut Option<Box<Node>>,
     scope: &mut Rc<RefCell<Scope>>,
     start: &mut Position,
     end: &mut Position,
 ) -> Result<ChValue, Error> {
     let mut n_scope = Scope::from_parent(String::from("<for>"), scope.clone(), Some(*start));
+    let mut values: Vec<ChValue> = Vec::new();
 
     if let Some(c) = c1 {
         visit_node(c, &mut n_scope)?;
     }
 
-    while visit_node(c2, &mut n_scope)?.is_true() {
-        visit_node(body, &mut n_scope)?;
+    loop {
+        if let Some(c) = c2 {
+            if !visit_node(c, &mut n_scope)?.is_true() {
+                break;
+            }
+        }
+
+        match visit_loop_body(body, &mut n_scope)? {
+            Some(value) => values.push(value),
+            None => return Ok(loop_result(values, start, end)),
+        }
         if let Some(c) = c3 {
             visit_node(c, &mut n_scope)?;
         }
     }
 
-    Ok(ChValue::None(ChNone {
-        start_pos: Some(*start),
-        end_pos: Some(*end),
-    }))
+    visit_loop_else(else_case, &mut n_scope)?;
+
+    Ok(loop_result(values, start, end))
+}
+
+fn visit_foreach_node(
+    var: &mut Token,
+    collection: &mut Node,
+    body: &mut Node,
+    else_case: &mut Option<Box<Node>>,
+    scope: &mut Rc<RefCell<Scope>>,
+    start: &mut Position,
+    end: &mut Position,
+) -> Result<ChValue, Error> {
+    let var_name = match &var.token_type {
+        TokenType::Id(s) => s.to_string(),
+        _ => {
+            return Err(Error::new(
+                ErrType::InvalidSyntax,
+                Some(var.start_pos),
+                Some(var.end_pos),
+                format!("expected ID found '{:?}'", var),
+                Some(scope.clone()),
+            ))
+        }
+    };
+
+    let items: Vec<ChValue> = match visit_node(collection, scope)? {
+        ChValue::Array(array) => array.into_iter().collect(),
+        ChValue::String(s) => s
+            .string
+            .chars()
+            .map(|c| {
+                ChValue::String(ChString {
+                    string: c.to_string(),
+                    start_pos: s.start_pos,
+                    end_pos: s.end_pos,
+                })
+            })
+            .collect(),
+        value => {
+            return Err(Error::new(
+                ErrType::Runtime,
+                value.get_start(),
+                value.get_end(),
+                format!("can not iterate over {}", value.type_name()),
+                Some(scope.clone()),
+            ))
+        }
+    };
+
+    let mut n_scope = Scope::from_parent(String::from("<for>"), scope.clone(), Some(*start));
+
+    let mut values: Vec<ChValue> = Vec::new();
+
+    for item in items {
+        n_scope.borrow_mut().set_mut(&var_name, item);
+        match visit_loop_body(body, &mut n_scope)? {
+            Some(value) => values.push(value),
+            None => return Ok(loop_result(values, start, end)),
+        }
+    }
+
+    visit_loop_else(else_case, &mut n_scope)?;
+
+    Ok(loop_result(values, start, end))
 }
 
 fn visit_while_node(
     condition: &mut Node,
     body: &mut Node,
+    else_case: &mut Option<Box<Node>>,
     scope: &mut Rc<RefCell<Scope>>,
     start: &mut Position,
     end: &mut Position,
 ) -> Result<ChValue, Error> {
     let mut n_scope = Scope::from_parent(String::from("<while>"), scope.clone(), Some(*start));
+    let mut values: Vec<ChValue> = Vec::new();
 
-    while visit_node(condition, scope)?.is_true() {
-        visit_node(body, &mut n_scope)?;
+    while visit_node(condition, &mut n_scope)?.is_true() {
+        match visit_loop_body(body, &mut n_scope)? {
+            Some(value) => values.push(value),
+            None => return Ok(loop_result(values, start, end)),
+        }
     }
 
-    Ok(ChValue::None(ChNone {
-        start_pos: Some(*start),
-        end_pos: Some(*end),
-    }))
+    visit_loop_else(else_case, &mut n_scope)?;
+    Ok(loop_result(values, start, end))
+}
+
+fn visit_dowhile_node(
+    body: &mut Node,
+    condition: &mut Node,
+    else_case: &mut Option<Box<Node>>,
+    scope: &mut Rc<RefCell<Scope>>,
+    start: &mut Position,
+    end: &mut Position,
+) -> Result<ChValue, Error> {
+    let mut n_scope = Scope::from_parent(String::from("<do-while>"), scope.clone(), Some(*start));
+    let mut values: Vec<ChValue> = Vec::new();
+
+    loop {
+        match visit_loop_body(body, &mut n_scope)? {
+            Some(value) => values.push(value),
+            None => return Ok(loop_result(values, start, end)),
+        }
+        if !visit_node(condition, &mut n_scope)?.is_true() {
+            break;
+        }
+    }
+
+    visit_loop_else(else_case, &mut n_scope)?;
+
+    Ok(loop_result(values, start, end))
+}
+
+//the handler runs in its own scope, with the error message bound to id
+fn visit_try_node(
+    body: &mut Node,
+    id: &mut Token,
+    handler: &mut Node,
+    scope: &mut Rc<RefCell<Scope>>,
+) -> Result<ChValue, Error> {
+    let error = match visit_node(body, scope) {
+        Err(e) if e.is_catchable() => e,
+        result => return result,
+    };
+
+    let var_name = expect_id(id)?;
+    let (start, end) = match error.span() {
+        Some((start, end)) => (Some(start), Some(end)),
+        None => (None, None),
+    };
+    let message = ChValue::String(ChString {
+        string: error.message().to_string(),
+        start_pos: start,
+        end_pos: end,
+    });
+
+    let mut n_scope = Scope::from_parent(String::from("<catch>"), scope.clone(), Some(id.start_pos));
+    n_scope.borrow_mut().set_mut(var_name, message);
+    visit_node(handler, &mut n_scope)
 }
 
 fn visit_funcdef_node(
     func_name: &mut Option<Token>,
-    args: &mut Vec<Token>,
+    args: &mut Vec<(Token, Option<Node>)>,
     body: &mut Node,
     start: &mut Position,
     end: &mut Position,
@@ -312,6 +968,15 @@ fn visit_funcdef_node(
     }
     .to_string();
 
+    //a named function gets a scope that only holds its own name, so it can
+    //call itself even after the outer variable is reassigned. A lambda
+    //assigned to a variable (f = fn(n) { f(n - 1) }) looks f up at call time
+    //like any other variable instead
+    let def_scope = match func_name {
+        Some(_) => Scope::from_parent(format!("<function: {}>", name), scope.clone(), Some(*start)),
+        None => scope.clone(),
+    };
+
     let func = ChValue::Function(ChFunction {
         func_type: FuncType::ChronFunc(Box::new(ChronosFunc {
             name: name.clone(),
@@ -319,11 +984,13 @@ fn visit_funcdef_node(
             body: body.clone(),
             start_pos: Some(*start),
             end_pos: Some(*end),
-            scope: scope.clone(),
+            scope: def_scope.clone(),
+            caller: None,
         })),
     });
 
     if func_name.is_some() {
+        def_scope.borrow_mut().set_mut(&name, func.clone());
         scope.borrow_mut().set_mut(&name, func.clone());
     }
 
@@ -335,19 +1002,12 @@ fn visit_call_node(
     args: &mut Vec<Node>,
     scope: &mut Rc<RefCell<Scope>>,
 ) -> Result<ChValue, Error> {
-    let name = match func_name {
-        Node::Access(tok) => {
-            if let TokenType::Id(s) = &tok.token_type {
-                Some(s.to_string())
-            } else {
-                None
-            }
-        }
-        _ => None,
+    let call_pos = func_name.span();
+    let (c, receiver) = match func_name {
+        Node::Member(node, member) => visit_method(node, member, scope)?,
+        _ => (visit_node(func_name, scope)?, None),
     };
 
-    let c = visit_node(func_name, scope)?;
-
     let mut call = match c {
         ChValue::Function(func) => func,
         _ => {
@@ -355,20 +1015,116 @@ fn visit_call_node(
                 ErrType::Runtime,
                 c.get_start(),
                 c.get_end(),
-                format!("expected Function found {}", c.get_desc()),
+                format!("expected Function found {}", c.type_name()),
                 Some(scope.clone()),
             ))
         }
     };
 
-    let mut arg_values: Vec<ChValue> = Vec::new();
+    //a variable names the function it holds, any other expression only
+    //names a lambda, 'fns[0]' says more than 'lambda'
+    let name = match (&*func_name, &call.func_type) {
+        (Node::Access(_), _) => call_name(func_name),
+        (Node::Member(_, member), _) if receiver.is_some() => expect_id(member).ok().cloned(),
+        (_, FuncType::ChronFunc(f)) if f.name == "lambda" => call_name(func_name),
+        _ => None,
+    };
+
+    let mut arg_values: Vec<ChValue> = receiver.into_iter().collect();
+    let mut named_values: Vec<(Token, ChValue)> = Vec::new();
 
     for arg in args {
-        arg_values.push(visit_node(arg, scope)?);
+        match arg {
+            Node::NamedArg(id, value) => named_values.push((id.clone(), visit_node(value, scope)?)),
+            _ => arg_values.push(visit_node(arg, scope)?),
+        }
     }
 
     call.set_scope(scope.clone());
-    call.execute(arg_values, name)
+
+    let catch_panics = scope.borrow().settings.catch_panics;
+    let result = match call.func_type {
+        FuncType::ChronFunc(ref mut func) if !named_values.is_empty() => {
+            func.execute_named(arg_values, named_values, name)
+        }
+        FuncType::RustFunc(_) if !named_values.is_empty() => {
+            let (id, _) = &named_values[0];
+            Err(Error::runtime_at(
+                "rust functions only take positional arguments",
+                Some(id.start_pos),
+                Some(id.end_pos),
+            ))
+        }
+        FuncType::RustFunc(_) if catch_panics => {
+            panic::catch_unwind(AssertUnwindSafe(|| call.execute(arg_values, name)))
+                .unwrap_or_else(|payload| Err(Error::runtime(panic_message(payload))))
+        }
+        _ => call.execute(arg_values, name),
+    };
+
+    result.map_err(|mut e| {
+        if !e.has_position() {
+            e.set_position(call_pos.0, call_pos.1);
+        }
+        if !e.has_scope() {
+            e.set_scope(scope.clone());
+        }
+        e
+    })
+}
+
+//'value.name(args)' calls a function stored in the map value under name,
+//anything else is 'name(value, args)' with name looked up in the scope, so
+//'"a,b".split(",")' is 'split("a,b", ",")'. Returns the callee and the
+//receiver to prepend to the arguments, if any
+fn visit_method(
+    node: &mut Node,
+    member: &mut Token,
+    scope: &mut Rc<RefCell<Scope>>,
+) -> Result<(ChValue, Option<ChValue>), Error> {
+    let value = visit_node(node, scope)?;
+
+    if let ChValue::Map(map) = &value {
+        if let Some(field) = map.get(expect_id(member)?) {
+            return Ok((field, None));
+        }
+    }
+
+    Ok((visit_access_node(member, scope)?, Some(value)))
+}
+
+//best-effort source like name of the called expression: 'f', 'fns[0]',
+//'make()' or 'ops["add"]', None for anything more complicated
+fn call_name(node: &Node) -> Option<String> {
+    match node {
+        Node::Access(tok) => expect_id(tok).ok().cloned(),
+        Node::Call(func, _) => Some(format!("{}()", call_name(func)?)),
+        Node::Member(value, member) => Some(format!("{}.{}", call_name(value)?, expect_id(member).ok()?)),
+        Node::ArrAccess(value, index) => {
+            let index = match index.as_ref() {
+                Node::Num(tok) | Node::String(tok) | Node::Access(tok) => match &tok.token_type {
+                    TokenType::Int(v) => v.to_string(),
+                    TokenType::String(s) => format!("{:?}", s),
+                    TokenType::Id(s) => s.to_string(),
+                    _ => String::from(".."),
+                },
+                _ => String::from(".."),
+            };
+            Some(format!("{}[{}]", call_name(value)?, index))
+        }
+        _ => None,
+    }
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    let message = match payload.downcast::<String>() {
+        Ok(s) => *s,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(s) => s.to_string(),
+            Err(_) => String::from("unknown cause"),
+        },
+    };
+    format!("rust function panicked: {}", message)
 }
 
 fn visit_array_node(
@@ -383,11 +1139,60 @@ fn visit_array_node(
         array.push(visit_node(v, scope)?);
     }
 
-    Ok(ChValue::Array(ChArray {
-        data: array,
-        start_pos: Some(*start),
-        end_pos: Some(*end),
-    }))
+    Ok(ChValue::Array(ChArray::new(array, Some(*start), Some(*end))))
+}
+
+fn visit_map_node(
+    entries: &mut Vec<(Node, Node)>,
+    start: &mut Position,
+    end: &mut Position,
+    scope: &mut Rc<RefCell<Scope>>,
+) -> Result<ChValue, Error> {
+    let mut data = BTreeMap::new();
+
+    for (key_node, value_node) in entries {
+        let key = match visit_node(key_node, scope)? {
+            ChValue::String(s) => s.string,
+            key => {
+                return Err(Error::new(
+                    ErrType::Runtime,
+                    key.get_start(),
+                    key.get_end(),
+                    format!("Map keys must be String, found: {}", key.type_name()),
+                    Some(scope.clone()),
+                ))
+            }
+        };
+        data.insert(key, visit_node(value_node, scope)?);
+    }
+
+    Ok(ChValue::Map(ChMap::new(data, Some(*start), Some(*end))))
+}
+
+//'map.key' is the same as 'map["key"]'
+fn visit_member_node(
+    node: &mut Node,
+    member: &mut Token,
+    scope: &mut Rc<RefCell<Scope>>,
+) -> Result<ChValue, Error> {
+    let value = visit_node(node, scope)?;
+
+    let name = expect_id(member)?;
+
+    let found = match &value {
+        ChValue::Map(map) => map.get(name),
+        _ => None,
+    };
+
+    found.ok_or_else(|| {
+        Error::new(
+            ErrType::Runtime,
+            Some(member.start_pos),
+            Some(member.end_pos),
+            format!("{} has no member {:?}", value.type_name(), name),
+            Some(scope.clone()),
+        )
+    })
 }
 
 fn visit_arraccess_node(
@@ -400,3 +1205,37 @@ fn visit_arraccess_node(
 
     name.access(index)
 }
+
+fn visit_slice_node(
+    value: &mut Node,
+    start: &mut Option<Box<Node>>,
+    end: &mut Option<Box<Node>>,
+    scope: &mut Rc<RefCell<Scope>>,
+) -> Result<ChValue, Error> {
+    let value = visit_node(value, scope)?;
+
+    let mut bound = |node: &mut Option<Box<Node>>| -> Result<Option<ChInt>, Error> {
+        let node = match node {
+            Some(node) => node,
+            None => return Ok(None),
+        };
+
+        match visit_node(node, scope)? {
+            ChValue::Number(ChNumber {
+                value: NumberType::Int(v),
+                ..
+            }) => Ok(Some(v)),
+            other => Err(Error::new(
+                ErrType::Runtime,
+                other.get_start(),
+                other.get_end(),
+                format!("slice bounds have to be Int, found: {}", other.type_name()),
+                Some(scope.clone()),
+            )),
+        }
+    };
+
+    let start = bound(start)?;
+    let end = bound(end)?;
+    value.slice(start, end)
+}
@@ -0,0 +1,379 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::rc::Rc;
+
+use crate::chronos::*;
+use crate::datatypes::*;
+use crate::errors::*;
+
+//Number -> number, String -> string, Bool -> true/false, None -> null,
+//Array -> array, Map -> object. Functions have no json representation
+pub fn to_json(value: &ChValue) -> Result<String, Error> {
+    let mut json = String::new();
+    write_value(value, &mut json, &mut Vec::new())?;
+    Ok(json)
+}
+
+//parents holds the arrays and maps that are currently being written,
+//finding one of them again means the value contains itself
+fn write_value(value: &ChValue, json: &mut String, parents: &mut Vec<*const ()>) -> Result<(), Error> {
+    match value {
+        ChValue::Number(n) => match n.value {
+            NumberType::Int(v) => write!(json, "{}", v).unwrap(),
+            NumberType::Float(v) if v.is_finite() => write!(json, "{}", v).unwrap(),
+            NumberType::Float(v) => return Err(not_serializable(&format!("the number {}", v))),
+        },
+        ChValue::String(s) => write_string(&s.string, json),
+        ChValue::Bool(b) => write!(json, "{}", b.value).unwrap(),
+        ChValue::None(_) => json.push_str("null"),
+        ChValue::Array(a) => {
+            enter(Rc::as_ptr(&a.data) as *const (), value, parents)?;
+            json.push('[');
+            for (i, item) in a.data.borrow().iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                write_value(item, json, parents)?;
+            }
+            json.push(']');
+            parents.pop();
+        }
+        ChValue::Map(m) => {
+            enter(Rc::as_ptr(&m.data) as *const (), value, parents)?;
+            json.push('{');
+            for (i, (key, item)) in m.data.borrow().iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                write_string(key, json);
+                json.push(':');
+                write_value(item, json, parents)?;
+            }
+            json.push('}');
+            parents.pop();
+        }
+        ChValue::Function(_) => return Err(not_serializable("Function")),
+    }
+    Ok(())
+}
+
+fn enter(ptr: *const (), value: &ChValue, parents: &mut Vec<*const ()>) -> Result<(), Error> {
+    if parents.contains(&ptr) {
+        return Err(not_serializable(&format!("{} containing itself", value.type_name())));
+    }
+    parents.push(ptr);
+    Ok(())
+}
+
+//nested values may carry positions from where they were created, the call
+//site of to_json is more useful
+fn not_serializable(what: &str) -> Error {
+    Error::runtime(format!("to_json: {} is not serializable", what))
+}
+
+fn write_string(s: &str, json: &mut String) {
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+//objects -> Map, arrays -> Array, null -> none. Integers that fit into a
+//ChInt become Int, every other number a Float
+pub fn from_json(text: &str) -> Result<ChValue, Error> {
+    let mut parser = JsonParser {
+        text,
+        index: 0,
+        line: 0,
+        column: 0,
+        depth: 0,
+    };
+
+    parser.skip_whitespace();
+    let value = parser.value()?;
+    parser.skip_whitespace();
+
+    match parser.peek() {
+        None => Ok(value),
+        Some(c) => Err(parser.error(&format!("unexpected '{}' after the value", c))),
+    }
+}
+
+//nesting limit, so that '[[[[...' can not overflow the stack
+const MAX_DEPTH: usize = 256;
+
+struct JsonParser<'a> {
+    text: &'a str,
+    //byte index into text
+    index: usize,
+    line: usize,
+    column: usize,
+    depth: usize,
+}
+
+impl JsonParser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.index..].chars().next()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.index += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 0;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, details: &str) -> Error {
+        Error::runtime(format!(
+            "from_json: {} at line {}, column {}",
+            details,
+            self.line + 1,
+            self.column + 1
+        ))
+    }
+
+    fn unexpected(&self, expected: &str) -> Error {
+        match self.peek() {
+            Some(c) => self.error(&format!("expected {} found '{}'", expected, c)),
+            None => self.error(&format!("expected {} found the end of the input", expected)),
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), Error> {
+        if self.peek() != Some(c) {
+            return Err(self.unexpected(&format!("'{}'", c)));
+        }
+        self.advance();
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t' | '\n' | '\r')) {
+            self.advance();
+        }
+    }
+
+    fn value(&mut self) -> Result<ChValue, Error> {
+        match self.peek() {
+            Some('{') => self.nested(JsonParser::object),
+            Some('[') => self.nested(JsonParser::array),
+            Some('"') => Ok(ChValue::String(ChString {
+                string: self.string()?,
+                start_pos: None,
+                end_pos: None,
+            })),
+            Some('-' | '0'..='9') => self.number(),
+            Some('t') => self.literal("true", ChValue::Bool(ChBool { value: true, start_pos: None, end_pos: None })),
+            Some('f') => self.literal("false", ChValue::Bool(ChBool { value: false, start_pos: None, end_pos: None })),
+            Some('n') => self.literal("null", ChValue::None(ChNone { start_pos: None, end_pos: None })),
+            _ => Err(self.unexpected("a value")),
+        }
+    }
+
+    fn nested(&mut self, parse: fn(&mut Self) -> Result<ChValue, Error>) -> Result<ChValue, Error> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error("nesting is too deep"));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn literal(&mut self, word: &str, value: ChValue) -> Result<ChValue, Error> {
+        if !self.text[self.index..].starts_with(word) {
+            return Err(self.unexpected(&format!("'{}'", word)));
+        }
+        for _ in word.chars() {
+            self.advance();
+        }
+        Ok(value)
+    }
+
+    fn object(&mut self) -> Result<ChValue, Error> {
+        let mut data = BTreeMap::new();
+        self.expect('{')?;
+        self.skip_whitespace();
+
+        if self.peek() == Some('}') {
+            self.advance();
+            return Ok(ChValue::Map(ChMap::new(data, None, None)));
+        }
+
+        loop {
+            if self.peek() != Some('"') {
+                return Err(self.unexpected("a string key"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            self.skip_whitespace();
+            data.insert(key, self.value()?);
+            self.skip_whitespace();
+
+            match self.advance() {
+                Some(',') => self.skip_whitespace(),
+                Some('}') => return Ok(ChValue::Map(ChMap::new(data, None, None))),
+                _ => return Err(self.error("expected ',' or '}' in object")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<ChValue, Error> {
+        let mut data = Vec::new();
+        self.expect('[')?;
+        self.skip_whitespace();
+
+        if self.peek() == Some(']') {
+            self.advance();
+            return Ok(ChValue::Array(ChArray::new(data, None, None)));
+        }
+
+        loop {
+            data.push(self.value()?);
+            self.skip_whitespace();
+
+            match self.advance() {
+                Some(',') => self.skip_whitespace(),
+                Some(']') => return Ok(ChValue::Array(ChArray::new(data, None, None))),
+                _ => return Err(self.error("expected ',' or ']' in array")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let mut s = String::new();
+        self.expect('"')?;
+
+        loop {
+            match self.advance() {
+                Some('"') => return Ok(s),
+                Some('\\') => s.push(self.escape()?),
+                Some(c) if (c as u32) < 0x20 => {
+                    return Err(self.error("control character in string, it has to be escaped"))
+                }
+                Some(c) => s.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, Error> {
+        let c = match self.advance() {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('/') => '/',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('u') => {
+                let high = self.hex4()?;
+                if !(0xd800..0xdc00).contains(&high) {
+                    return char::from_u32(high).ok_or_else(|| self.error("invalid unicode escape"));
+                }
+
+                //characters outside the basic plane are escaped as a surrogate pair
+                if !self.text[self.index..].starts_with("\\u") {
+                    return Err(self.error("expected the second half of a surrogate pair"));
+                }
+                self.advance();
+                self.advance();
+                let low = self.hex4()?;
+                if !(0xdc00..0xe000).contains(&low) {
+                    return Err(self.error("invalid surrogate pair"));
+                }
+                let code = 0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00);
+                return char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"));
+            }
+            _ => return Err(self.error("invalid escape sequence")),
+        };
+        Ok(c)
+    }
+
+    fn hex4(&mut self) -> Result<u32, Error> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self.peek().and_then(|c| c.to_digit(16));
+            match digit {
+                Some(d) => code = code * 16 + d,
+                None => return Err(self.unexpected("a hex digit")),
+            }
+            self.advance();
+        }
+        Ok(code)
+    }
+
+    //-?(0|[1-9][0-9]*)(.[0-9]+)?([eE][+-]?[0-9]+)?
+    fn number(&mut self) -> Result<ChValue, Error> {
+        let start = self.index;
+        let mut is_int = true;
+
+        if self.peek() == Some('-') {
+            self.advance();
+        }
+
+        match self.peek() {
+            Some('0') => {
+                self.advance();
+            }
+            Some('1'..='9') => self.digits(),
+            _ => return Err(self.unexpected("a digit")),
+        }
+
+        if self.peek() == Some('.') {
+            is_int = false;
+            self.advance();
+            if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return Err(self.unexpected("a digit"));
+            }
+            self.digits();
+        }
+
+        if matches!(self.peek(), Some('e' | 'E')) {
+            is_int = false;
+            self.advance();
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.advance();
+            }
+            if !self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                return Err(self.unexpected("a digit"));
+            }
+            self.digits();
+        }
+
+        let text = &self.text[start..self.index];
+        let value = match text.parse::<ChInt>() {
+            Ok(v) if is_int => NumberType::Int(v),
+            _ => NumberType::Float(text.parse::<ChFloat>().unwrap()),
+        };
+
+        Ok(ChValue::Number(ChNumber {
+            value,
+            start_pos: None,
+            end_pos: None,
+            literal: None,
+        }))
+    }
+
+    fn digits(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.advance();
+        }
+    }
+}
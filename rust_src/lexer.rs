@@ -2,158 +2,236 @@ use crate::chronos::*;
 use crate::errors::*;
 
 pub struct Lexer {
-    text: Box<[u8]>,
+    text: Box<str>,
     position: Position,
     current_char: Option<char>,
+    finished: bool,
+    preserve_literals: bool,
+    //newlines end statements for the parser, other users of the tokens can
+    //keep skipping them
+    emit_newlines: bool,
+    keep_comments: bool,
+}
+
+//true if the text has more opening than closing brackets, lexer errors
+//count as complete so that they get reported when interpreting
+pub fn is_incomplete(text: &str) -> bool {
+    let mut depth: i32 = 0;
+
+    for token in Lexer::new(0, 0, 0, text.to_string()) {
+        match token {
+            Ok(t) => match t.token_type {
+                TokenType::LRound | TokenType::LCurly | TokenType::LBrace => depth += 1,
+                TokenType::RRound | TokenType::RCurly | TokenType::RBrace => depth -= 1,
+                _ => (),
+            },
+            Err(_) => return false,
+        }
+    }
+
+    depth > 0
+}
+
+impl Iterator for Lexer {
+    type Item = Result<Token, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let token = self.next_token();
+        self.finished = match &token {
+            Ok(t) => matches!(t.token_type, TokenType::Eof),
+            Err(_) => true,
+        };
+        Some(token)
+    }
 }
 
 impl Lexer {
     pub fn new(file_nr: usize, line: usize, offset: usize, text: String) -> Self {
         let mut l = Lexer {
-            text: (text.as_bytes().into()),
+            text: text.into(),
             position: Position {
-                //file_name: Rc::new(file_name),
                 file_nr,
                 index: 0,
                 offset,
                 line,
                 column: 0,
-                //text: Rc::new(text),
             },
             current_char: None,
+            finished: false,
+            preserve_literals: false,
+            emit_newlines: false,
+            keep_comments: false,
         };
-        l.current_char = Some(l.text[l.position.index] as char);
+        l.current_char = l.text.chars().next();
+
+        //a '#!' line is only skipped at the very start of a file
+        if offset == 0 && l.text.starts_with("#!") {
+            while !matches!(l.current_char, None | Some('\n')) {
+                l.advance();
+            }
+        }
         l
     }
 
     fn advance(&mut self) {
         self.position.advance(&self.current_char);
 
-        self.current_char = if self.position.index < self.text.len() {
-            Some(self.text[self.position.index] as char)
-        } else {
-            None
-        };
+        self.current_char = self.text.get(self.position.index..).and_then(|s| s.chars().next());
     }
 
-    pub fn parse_tokens(&mut self) -> Result<Vec<Token>, Error> {
-        let mut tokens: Vec<Token> = Vec::new();
+    fn peek(&self) -> Option<char> {
+        let next = self.position.index + self.current_char.map_or(1, char::len_utf8);
+        self.text.get(next..).and_then(|s| s.chars().next())
+    }
 
-        while self.current_char != None {
-            let c = self.current_char.unwrap();
+    //lexes a block comment, comments can be nested: /* a /* b */ c */ has
+    //the text ' a /* b */ c '
+    fn make_comment(&mut self) -> Result<Token, Error> {
+        let start = self.position;
+        let mut depth = 0;
 
-            if " \t\n".contains(c) {
-                self.advance();
-            } else if match c {
-                '+' => {
-                    tokens.push(self.make_add()?);
-                    true
-                }
-                '-' => {
-                    tokens.push(self.make_sub()?);
-                    true
-                }
-                '/' => {
-                    tokens.push(Token::new(TokenType::Div, self.position, None));
-                    self.advance();
-                    true
-                }
-                '*' => {
-                    tokens.push(Token::new(TokenType::Mul, self.position, None));
-                    self.advance();
-                    true
-                }
-                '"' | '\'' => {
-                    tokens.push(self.make_string()?);
-                    true
-                }
-                '^' => {
-                    tokens.push(Token::new(TokenType::Pow, self.position, None));
-                    self.advance();
-                    true
-                }
-                '(' => {
-                    tokens.push(Token::new(TokenType::LRound, self.position, None));
-                    self.advance();
-                    true
-                }
-                ')' => {
-                    tokens.push(Token::new(TokenType::RRound, self.position, None));
+        while let Some(c) = self.current_char {
+            match (c, self.peek()) {
+                ('/', Some('*')) => {
+                    depth += 1;
                     self.advance();
-                    true
                 }
-                '{' => {
-                    tokens.push(Token::new(TokenType::LCurly, self.position, None));
+                ('*', Some('/')) => {
+                    depth -= 1;
                     self.advance();
-                    true
                 }
-                '}' => {
-                    tokens.push(Token::new(TokenType::RCurly, self.position, None));
-                    self.advance();
-                    true
-                }
-                '[' => {
-                    tokens.push(Token::new(TokenType::LBrace, self.position, None));
-                    self.advance();
-                    true
+                _ => (),
+            }
+            self.advance();
+
+            if depth == 0 {
+                let text = &self.text[start.index + 2..self.position.index - 2];
+                return Ok(Token::new(
+                    TokenType::Comment(text.to_string()),
+                    start,
+                    Some(self.position),
+                ));
+            }
+        }
+
+        let mut end = start;
+        end.advance(&Some('/'));
+        end.advance(&Some('*'));
+
+        Err(Error::new(
+            ErrType::ExpectedChar,
+            Some(start),
+            Some(end),
+            "Lexer: unterminated comment, expected '*/'".to_string(),
+            None,
+        ))
+    }
+
+    pub fn set_preserve_literals(&mut self, preserve_literals: bool) {
+        self.preserve_literals = preserve_literals;
+    }
+
+    pub fn set_emit_newlines(&mut self, emit_newlines: bool) {
+        self.emit_newlines = emit_newlines;
+    }
+
+    pub fn set_keep_comments(&mut self, keep_comments: bool) {
+        self.keep_comments = keep_comments;
+    }
+
+    pub fn parse_tokens(&mut self) -> Result<Vec<Token>, Error> {
+        self.collect()
+    }
+
+    fn make_single(&mut self, token_type: TokenType) -> Token {
+        let token = Token::new(token_type, self.position, None);
+        self.advance();
+        token
+    }
+
+    //lexes 'op' or 'op='
+    fn make_with_equal(&mut self, token_type: TokenType, eq_type: TokenType) -> Token {
+        let start = self.position;
+        self.advance();
+
+        if self.current_char == Some('=') {
+            self.advance();
+            return Token::new(eq_type, start, Some(self.position));
+        }
+
+        Token::new(token_type, start, None)
+    }
+
+    fn next_token(&mut self) -> Result<Token, Error> {
+        while let Some(c) = self.current_char {
+            if c == '\n' && self.emit_newlines {
+                return Ok(self.make_single(TokenType::Newline));
+            }
+
+            if " \t\n".contains(c) {
+                self.advance();
+                continue;
+            }
+
+            if c == '/' && self.peek() == Some('*') {
+                let comment = self.make_comment()?;
+                if self.keep_comments {
+                    return Ok(comment);
                 }
-                ']' => {
-                    tokens.push(Token::new(TokenType::RBrace, self.position, None));
+                continue;
+            }
+
+            return match c {
+                '+' => self.make_add(),
+                '-' => self.make_sub(),
+                '/' => Ok(self.make_with_equal(TokenType::Div, TokenType::DivEq)),
+                '*' => Ok(self.make_with_equal(TokenType::Mul, TokenType::MulEq)),
+                '%' => Ok(self.make_with_equal(TokenType::Mod, TokenType::ModEq)),
+                '"' | '\'' => self.make_string(),
+                '^' => Ok(self.make_single(TokenType::Pow)),
+                '(' => Ok(self.make_single(TokenType::LRound)),
+                ')' => Ok(self.make_single(TokenType::RRound)),
+                '{' => Ok(self.make_single(TokenType::LCurly)),
+                '}' => Ok(self.make_single(TokenType::RCurly)),
+                '[' => Ok(self.make_single(TokenType::LBrace)),
+                ']' => Ok(self.make_single(TokenType::RBrace)),
+                ',' => Ok(self.make_single(TokenType::Comma)),
+                ';' => Ok(self.make_single(TokenType::Semicln)),
+                '?' if self.peek() == Some('?') => {
+                    let start = self.position;
                     self.advance();
-                    true
-                }
-                ',' => {
-                    tokens.push(Token::new(TokenType::Comma, self.position, None));
                     self.advance();
-                    true
+                    Ok(Token::new(TokenType::Coalesce, start, Some(self.position)))
                 }
-                ';' => {
-                    tokens.push(Token::new(TokenType::Semicln, self.position, None));
+                '?' => Ok(self.make_single(TokenType::Question)),
+                ':' => Ok(self.make_single(TokenType::Colon)),
+                '.' => Ok(self.make_single(TokenType::Dot)),
+                '=' => Ok(self.make_equal()),
+                '!' => self.make_not(),
+                '<' => Ok(self.make_less()),
+                '>' => Ok(self.make_greater()),
+                '&' | '|' => self.make_keyword(),
+                _ if c.is_alphabetic() => Ok(self.make_identifier()),
+                _ if DIGITS.contains(c) => self.make_number(),
+                _ => {
+                    let start_pos = self.position;
                     self.advance();
-                    true
+                    Err(Error::new(
+                        ErrType::IllegalChar,
+                        Some(start_pos),
+                        Some(self.position),
+                        format!("Lexer: found '{}'", c),
+                        None,
+                    ))
                 }
-                '=' => {
-                    tokens.push(self.make_equal());
-                    true
-                }
-                '!' => {
-                    tokens.push(self.make_not()?);
-                    true
-                }
-                '<' => {
-                    tokens.push(self.make_less());
-                    true
-                }
-                '>' => {
-                    tokens.push(self.make_greater());
-                    true
-                }
-
-                '&' | '|' => {
-                    tokens.push(self.make_keyword()?);
-                    true
-                }
-                _ => false,
-            } {
-            } else if LETTERS.contains(c) {
-                tokens.push(self.make_identifier());
-            } else if DIGITS.contains(c) {
-                tokens.push(self.make_number());
-            } else {
-                let start_pos = self.position;
-                self.advance();
-                return Err(Error::new(
-                    ErrType::IllegalChar,
-                    Some(start_pos),
-                    Some(self.position),
-                    format!("Lexer: found '{}'", c),
-                    None,
-                ));
-            }
+            };
         }
 
-        tokens.push(Token::new(TokenType::Eof, self.position, None));
-        Ok(tokens)
+        Ok(Token::new(TokenType::Eof, self.position, None))
     }
 
     fn make_string(&mut self) -> Result<Token, Error> {
@@ -170,19 +248,15 @@ impl Lexer {
         self.advance();
         let end = self.position;
 
-        Ok(Token {
-            token_type: TokenType::String(s),
-            start_pos: start,
-            end_pos: end,
-        })
+        Ok(Token::new(TokenType::String(s), start, Some(end)))
     }
 
     fn make_add(&mut self) -> Result<Token, Error> {
         let start = self.position;
         self.advance();
 
-        match self.current_char.unwrap() {
-            '=' => {
+        match self.current_char {
+            Some('=') => {
                 self.advance();
                 Ok(Token::new(
                     TokenType::AddEq,
@@ -190,6 +264,14 @@ impl Lexer {
                     Some(self.position),
                 ))
             }
+            Some('+') => {
+                self.advance();
+                Ok(Token::new(
+                    TokenType::PlusPlus,
+                    start,
+                    Some(self.position),
+                ))
+            }
             _ => Ok(Token::new(TokenType::Add, start, None)),
         }
     }
@@ -198,8 +280,8 @@ impl Lexer {
         let start = self.position;
         self.advance();
 
-        match self.current_char.unwrap() {
-            '=' => {
+        match self.current_char {
+            Some('=') => {
                 self.advance();
                 Ok(Token::new(
                     TokenType::SubEq,
@@ -207,6 +289,14 @@ impl Lexer {
                     Some(self.position),
                 ))
             }
+            Some('-') => {
+                self.advance();
+                Ok(Token::new(
+                    TokenType::MinusMinus,
+                    start,
+                    Some(self.position),
+                ))
+            }
             _ => Ok(Token::new(TokenType::Sub, start, None)),
         }
     }
@@ -303,10 +393,9 @@ impl Lexer {
         let mut id = String::from("");
         let pos_start = self.position;
 
-        let allowed = LETTERS.to_owned() + "_";
-
-        while self.current_char != None && allowed.contains(self.current_char.unwrap()) {
-            id.push(self.current_char.unwrap());
+        //letters, digits and '_' of any script, the first char is a letter
+        while let Some(c) = self.current_char.filter(|c| c.is_alphanumeric() || *c == '_') {
+            id.push(c);
             self.advance();
         }
 
@@ -318,18 +407,24 @@ impl Lexer {
     }
 
     //TODO: don't use strings
-    fn make_number(&mut self) -> Token {
+    fn make_number(&mut self) -> Result<Token, Error> {
         let mut num: String = String::new();
         let mut dot_count: u8 = 0;
 
         let start = self.position;
 
-        let s = DIGITS.to_owned() + ".";
+        let s = DIGITS.to_owned() + "._";
 
         while self.current_char != None && (s).contains(self.current_char.unwrap()) {
             let c = self.current_char.unwrap();
-            if c == '.' {
-                if dot_count >= 1 {
+            if c == '_' {
+                self.digit_separator(start, &num)?;
+                continue;
+            } else if c == '.' {
+                //'3.' and '.5' are not numbers, the dot is lexed on its own
+                //so that '3.foo' stays member access
+                let digit_follows = self.peek().is_some_and(|n| DIGITS.contains(n));
+                if dot_count >= 1 || !digit_follows {
                     break;
                 }
                 dot_count += 1;
@@ -341,18 +436,128 @@ impl Lexer {
             self.advance();
         }
 
-        if dot_count == 0 {
-            return Token::new(
-                TokenType::Int(num.parse::<ChInt>().unwrap()),
-                start,
-                Some(self.position),
-            );
+        //'1e3', '2.5e-4' or '6E23' are Floats, an 'e' followed by a letter
+        //is left alone ('1else'), one without digits is an error
+        let mut exponent = false;
+        if let Some(c @ ('e' | 'E')) = self.current_char {
+            match self.peek() {
+                Some(n) if n.is_alphabetic() || n == '_' => (),
+                Some(n) if DIGITS.contains(n) || n == '+' || n == '-' => {
+                    exponent = true;
+                    num.push(c);
+                    self.advance();
+
+                    if let Some(sign @ ('+' | '-')) = self.current_char {
+                        num.push(sign);
+                        self.advance();
+                    }
+                    if !self.current_char.is_some_and(|n| DIGITS.contains(n)) {
+                        return Err(self.missing_exponent(start, &num));
+                    }
+                    while let Some(d) = self.current_char.filter(|d| DIGITS.contains(*d) || *d == '_') {
+                        if d == '_' {
+                            self.digit_separator(start, &num)?;
+                            continue;
+                        }
+                        num.push(d);
+                        self.advance();
+                    }
+                }
+                _ => {
+                    num.push(c);
+                    self.advance();
+                    return Err(self.missing_exponent(start, &num));
+                }
+            }
+        }
+
+        //a suffix decides the type over the dot: 3f is the Float 3.0 and 3.5i
+        //the Int 3, the fraction is cut off (rounds toward zero). It only
+        //counts as a suffix if no identifier follows directly, e.g. 3in
+        let suffix = match self.current_char {
+            Some(c @ ('i' | 'f')) if !self.peek().is_some_and(|n| n.is_alphanumeric() || n == '_') => {
+                self.advance();
+                Some(c)
+            }
+            _ => None,
+        };
+
+        if suffix == Some('i') && exponent {
+            let v = num.parse::<ChFloat>().unwrap().trunc();
+            if v < ChInt::MIN as ChFloat || v > ChInt::MAX as ChFloat {
+                return Err(Error::new(
+                    ErrType::InvalidSyntax,
+                    Some(start),
+                    Some(self.position),
+                    format!("Lexer: integer literal {} is too large", num),
+                    None,
+                ));
+            }
+            return Ok(Token::new(TokenType::Int(v as ChInt), start, Some(self.position)));
         }
 
-        Token::new(
-            TokenType::Float(num.parse::<ChFloat>().unwrap()),
-            start,
+        if suffix == Some('i') {
+            num.truncate(num.find('.').unwrap_or(num.len()));
+            dot_count = 0;
+        }
+
+        let (token_type, printed) = if suffix == Some('f') {
+            let v = num.parse::<ChFloat>().unwrap();
+            (TokenType::Float(v), v.to_string())
+        } else if dot_count == 0 && !exponent {
+            let v = match num.parse::<ChInt>() {
+                Ok(v) => v,
+                Err(_) => {
+                    return Err(Error::new(
+                        ErrType::InvalidSyntax,
+                        Some(start),
+                        Some(self.position),
+                        format!("Lexer: integer literal {} is too large", num),
+                        None,
+                    ))
+                }
+            };
+            (TokenType::Int(v), v.to_string())
+        } else {
+            let v = num.parse::<ChFloat>().unwrap();
+            (TokenType::Float(v), v.to_string())
+        };
+
+        //the literal keeps the separators, num has them removed
+        let source = &self.text[start.index..self.position.index];
+        let mut token = Token::new(token_type, start, Some(self.position));
+        if self.preserve_literals && suffix.is_none() && printed != source {
+            token.literal = Some(source.into());
+        }
+        Ok(token)
+    }
+
+    //skips a '_' in a number, it has to sit between two digits: '1_000' but
+    //not '1_', '1__0' or '1_.5'
+    fn digit_separator(&mut self, start: Position, num: &str) -> Result<(), Error> {
+        let after_digit = num.ends_with(|c| DIGITS.contains(c));
+        let before_digit = self.peek().is_some_and(|n| DIGITS.contains(n));
+        self.advance();
+
+        if after_digit && before_digit {
+            return Ok(());
+        }
+        Err(Error::new(
+            ErrType::IllegalChar,
+            Some(start),
+            Some(self.position),
+            String::from("Lexer: '_' in a number has to be between two digits"),
+            None,
+        ))
+    }
+
+    fn missing_exponent(&self, start: Position, num: &str) -> Error {
+        Error::new(
+            ErrType::ExpectedChar,
+            Some(start),
             Some(self.position),
+            format!("Lexer: expected digits after the exponent of '{}'", num),
+            None,
         )
     }
 }
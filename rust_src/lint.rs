@@ -0,0 +1,65 @@
+use std::collections::HashSet;
+
+use crate::chronos::*;
+use crate::errors::*;
+
+//checks that run on the AST before it is interpreted, they only report
+//suspicious code and never stop the evaluation
+pub fn check(ast: &Node) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    check_node(ast, &mut warnings);
+    warnings
+}
+
+fn check_node(node: &Node, warnings: &mut Vec<Warning>) {
+    if let Node::FuncDef(_, _, body, ..) = node {
+        unused_locals(body, warnings);
+    }
+
+    for child in node.children() {
+        check_node(child, warnings);
+    }
+}
+
+//a variable declared with let inside a function that is never read, reads
+//from nested functions count as well since they may capture it
+fn unused_locals(body: &Node, warnings: &mut Vec<Warning>) {
+    let mut declared: Vec<&Token> = Vec::new();
+    let mut used: HashSet<&str> = HashSet::new();
+    collect_locals(body, &mut declared, &mut used, true);
+
+    for id in declared {
+        if let TokenType::Id(name) = &id.token_type {
+            if !used.contains(name.as_str()) {
+                warnings.push(Warning::new(
+                    id.start_pos,
+                    id.end_pos,
+                    format!("unused variable {:?}", name),
+                ));
+            }
+        }
+    }
+}
+
+fn collect_locals<'a>(
+    node: &'a Node,
+    declared: &mut Vec<&'a Token>,
+    used: &mut HashSet<&'a str>,
+    own_body: bool,
+) {
+    match node {
+        Node::Let(id, _) | Node::Const(id, _) if own_body => declared.push(id),
+        Node::Access(Token {
+            token_type: TokenType::Id(name),
+            ..
+        }) => {
+            used.insert(name);
+        }
+        _ => (),
+    }
+
+    let own_body = own_body && !matches!(node, Node::FuncDef(..));
+    for child in node.children() {
+        collect_locals(child, declared, used, own_body);
+    }
+}
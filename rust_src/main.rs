@@ -5,28 +5,57 @@ mod compiler;
 mod datatypes;
 mod errors;
 mod interpreter;
+mod json;
 mod lexer;
+mod lint;
 mod parser;
 
 use chronos::*;
+use errors::Signal;
 use std::io::{self, Write};
+use std::thread;
+
+//every chronos call takes a few rust frames, the REPL runs on a bigger stack
+//than the main thread so that it can allow deeper recursion
+const STACK_SIZE: usize = 64 * 1024 * 1024;
+const MAX_CALL_DEPTH: usize = 1000;
 
 fn main() {
+    let repl = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(repl)
+        .expect("could not start the interpreter thread");
+
+    let _ = repl.join();
+}
+
+fn repl() {
     let mut c = Compiler::new();
+    c.set_max_call_depth(MAX_CALL_DEPTH);
+    let mut prompt = "chronos > ";
 
     loop {
         let mut buffer = String::new();
 
-        print!("chronos > ");
+        print!("{}", prompt);
         let _ = io::stdout().flush();
-        io::stdin()
+        let read = io::stdin()
             .read_line(&mut buffer)
             .expect("Error while reading from STDIN");
 
-        match c.interpret(String::from("<stdin>"), buffer) {
-            Ok(result) => println!("{}", result),
-            Err(mut e) => {
-                e.set_files(c.file_manager.files.clone());
+        if read == 0 {
+            break;
+        }
+
+        prompt = "chronos > ";
+        match c.interpret_line(&buffer) {
+            InterpretOutcome::Complete(result) => println!("{}", result.repl_display()),
+            InterpretOutcome::NeedMoreInput => prompt = "........ > ",
+            InterpretOutcome::Error(mut e) => {
+                if let Some(Signal::Exit(code)) = e.signal() {
+                    std::process::exit(code);
+                }
+                e.set_files(c.files());
                 e.print();
             }
         }
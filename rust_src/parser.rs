@@ -5,48 +5,116 @@ pub struct Parser {
     tokens: Vec<Token>,
     token_index: usize,
     current_token: Token,
+    //a Newline token came right before current_token
+    newline: bool,
+    in_brackets: bool,
+    //parsing a loop body, but not a function inside it
+    in_loop: bool,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
         let t = tokens[0].clone();
-        Parser {
+        let mut p = Parser {
             tokens,
             token_index: 0,
             current_token: t,
-        }
+            newline: false,
+            in_brackets: false,
+            in_loop: false,
+        };
+        p.load_token();
+        p
     }
 
     pub fn parse(&mut self) -> Result<Node, Error> {
-        let nodes = self.expression()?;
-
-        match self.current_token.token_type {
-            TokenType::Eof => Ok(nodes),
-
-            _ => Err(Error::new(
-                ErrType::InvalidSyntax,
-                Some(self.current_token.start_pos),
-                Some(self.current_token.end_pos),
-                format!(
-                    "Parser: expected EOF found {:?}",
-                    self.current_token.token_type
-                ),
-                None,
-            )),
+        let mut nodes = self.statements(TokenType::Eof)?;
+
+        match nodes.len() {
+            //reports what an expression may start with
+            0 => self.expression(),
+            1 => Ok(nodes.pop().unwrap()),
+            _ => {
+                let start = nodes.first().unwrap().span().0;
+                let end = nodes.last().unwrap().span().1;
+                Ok(Node::Block(nodes, start, end))
+            }
         }
     }
 
+    //a whole file, unlike parse an empty one is not an error
+    pub fn parse_file(&mut self) -> Result<Node, Error> {
+        let start = self.current_token.start_pos;
+        let nodes = self.statements(TokenType::Eof)?;
+        Ok(Node::Block(nodes, start, self.current_token.end_pos))
+    }
+
     fn advance(&mut self) {
         self.token_index += 1;
+        self.newline = false;
+        self.load_token();
+    }
 
-        if self.token_index < self.tokens.len() {
-            self.current_token = self.tokens[self.token_index].clone();
+    //Newline and Comment tokens never reach the grammar, a Newline only marks
+    //the token after it, see on_new_line
+    fn load_token(&mut self) {
+        while let Some(t) = self.tokens.get(self.token_index) {
+            match t.token_type {
+                TokenType::Newline => self.newline = true,
+                TokenType::Comment(_) => (),
+                _ => {
+                    self.current_token = t.clone();
+                    return;
+                }
+            }
+            self.token_index += 1;
         }
     }
 
     fn retreat(&mut self) {
+        let skipped = |t: &Token| matches!(t.token_type, TokenType::Newline | TokenType::Comment(_));
+
         self.token_index -= 1;
+        while skipped(&self.tokens[self.token_index]) {
+            self.token_index -= 1;
+        }
         self.current_token = self.tokens[self.token_index].clone();
+        self.newline = self.tokens[..self.token_index]
+            .iter()
+            .rev()
+            .take_while(|t| skipped(t))
+            .any(|t| matches!(t.token_type, TokenType::Newline));
+    }
+
+    //outside of brackets a token on a new line starts the next statement, so
+    //operators, calls and indexing do not continue the expression before it
+    fn on_new_line(&self) -> bool {
+        self.newline && !self.in_brackets
+    }
+
+    //newlines inside (), [] and map literals are only whitespace, a block
+    //has statements again even inside brackets
+    fn with_brackets<T>(
+        &mut self,
+        in_brackets: bool,
+        parse: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let outer = std::mem::replace(&mut self.in_brackets, in_brackets);
+        let result = parse(self);
+        self.in_brackets = outer;
+        result
+    }
+
+    //'break' is only allowed in loop bodies, a function body starts over
+    fn with_loop<T>(
+        &mut self,
+        in_loop: bool,
+        parse: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let outer = std::mem::replace(&mut self.in_loop, in_loop);
+        let result = parse(self);
+        self.in_loop = outer;
+        result
     }
 
     fn atom(&mut self) -> Result<Node, Error> {
@@ -67,7 +135,7 @@ impl Parser {
             }
             TokenType::LRound => {
                 self.advance();
-                let expr = self.expression()?;
+                let expr = self.with_brackets(true, Parser::expression)?;
                 match self.current_token.token_type {
                     TokenType::RRound => {
                         self.advance();
@@ -87,9 +155,16 @@ impl Parser {
             }
             TokenType::Keywrd(Keyword::If) => self.if_expression(),
             TokenType::LBrace => self.array_expression(),
+            TokenType::LCurly => self.map_expression(),
             TokenType::Keywrd(Keyword::While) => self.while_expression(),
+            TokenType::Keywrd(Keyword::Do) => self.do_while_expression(),
+            TokenType::Keywrd(Keyword::Try) => self.try_expression(),
             TokenType::Keywrd(Keyword::For) => self.for_expression(),
             TokenType::Keywrd(Keyword::Func) => self.func_expression(),
+            TokenType::Keywrd(Keyword::Del) => self.del_expression(),
+            TokenType::Keywrd(Keyword::Throw) => self.throw_expression(),
+            TokenType::Keywrd(Keyword::Import) => self.import_expression(),
+            TokenType::Keywrd(Keyword::Break) => self.break_expression(),
             _ => Err(Error::new(
                 ErrType::InvalidSyntax,
                 Some(t.start_pos),
@@ -113,44 +188,136 @@ impl Parser {
     }
 
     fn wrap_callable(&mut self, node: Node) -> Result<Node, Error> {
-        if matches!(self.current_token.token_type, TokenType::LRound) {
+        if self.on_new_line() {
+            Ok(node)
+        } else if matches!(self.current_token.token_type, TokenType::LRound) {
             self.advance();
-            let mut arg_nodes: Vec<Node> = Vec::new();
-
-            if !matches!(self.current_token.token_type, TokenType::RRound) {
-                arg_nodes.push(self.expression()?);
-
-                while matches!(self.current_token.token_type, TokenType::Comma) {
-                    self.advance();
-                    arg_nodes.push(self.expression()?);
-                }
-
-                if !matches!(self.current_token.token_type, TokenType::RRound,) {
-                    return Err(Error::new(
-                        ErrType::InvalidSyntax,
-                        Some(self.current_token.start_pos),
-                        Some(self.current_token.end_pos),
-                        format!("Parser: expected RROUND found '{:?}'", self.current_token),
-                        None,
-                    ));
-                }
-            }
+            let arg_nodes = self.with_brackets(true, Parser::arguments)?;
             self.advance();
             Ok(self.wrap_callable(Node::Call(node.into(), arg_nodes))?)
         } else if matches!(self.current_token.token_type, TokenType::LBrace) {
             self.advance();
-            let indx = self.expression()?;
+            let indexed = self.with_brackets(true, |p| p.index(node))?;
             self.expect_token(TokenType::RBrace)?;
             self.advance();
-            Ok(self.wrap_callable(Node::ArrAccess(node.into(), indx.into()))?)
+            Ok(self.wrap_callable(indexed)?)
+        } else if matches!(self.current_token.token_type, TokenType::Dot) {
+            self.advance();
+            self.expect_token(TokenType::Id(String::new()))?;
+            let member = self.current_token.clone();
+            self.advance();
+            Ok(self.wrap_callable(Node::Member(node.into(), member))?)
         } else {
             Ok(node)
         }
     }
 
+    //'value[index]' or a slice 'value[start:end]' where both bounds are
+    //optional, up to the closing ']'
+    fn index(&mut self, node: Node) -> Result<Node, Error> {
+        let bound = |p: &mut Parser| -> Result<Option<Box<Node>>, Error> {
+            match p.current_token.token_type {
+                TokenType::Colon | TokenType::RBrace => Ok(None),
+                _ => Ok(Some(p.expression()?.into())),
+            }
+        };
+
+        let start = bound(self)?;
+
+        if !matches!(self.current_token.token_type, TokenType::Colon) {
+            return match start {
+                Some(index) => Ok(Node::ArrAccess(node.into(), index)),
+                None => Err(self.unexpected_index()),
+            };
+        }
+        self.advance();
+
+        let end = bound(self)?;
+        Ok(Node::Slice(node.into(), start, end))
+    }
+
+    fn unexpected_index(&self) -> Error {
+        Error::new(
+            ErrType::InvalidSyntax,
+            Some(self.current_token.start_pos),
+            Some(self.current_token.end_pos),
+            format!(
+                "Parser: expected an index or ':' found {:?}",
+                self.current_token.token_type
+            ),
+            None,
+        )
+    }
+
+    //call arguments up to the closing ')', a trailing comma is allowed
+    fn arguments(&mut self) -> Result<Vec<Node>, Error> {
+        let mut arg_nodes: Vec<Node> = Vec::new();
+
+        if !matches!(self.current_token.token_type, TokenType::RRound) {
+            arg_nodes.push(self.argument(false)?);
+
+            while matches!(self.current_token.token_type, TokenType::Comma) {
+                self.advance();
+                if matches!(self.current_token.token_type, TokenType::RRound) {
+                    break;
+                }
+                let after_named = matches!(arg_nodes.last(), Some(Node::NamedArg(..)));
+                arg_nodes.push(self.argument(after_named)?);
+            }
+
+            if !matches!(self.current_token.token_type, TokenType::RRound,) {
+                return Err(Error::new(
+                    ErrType::InvalidSyntax,
+                    Some(self.current_token.start_pos),
+                    Some(self.current_token.end_pos),
+                    format!("Parser: expected RROUND found '{:?}'", self.current_token),
+                    None,
+                ));
+            }
+        }
+        Ok(arg_nodes)
+    }
+
+    //'value' or 'name = value', positional arguments come first
+    fn argument(&mut self, after_named: bool) -> Result<Node, Error> {
+        if let TokenType::Id(_) = self.current_token.token_type {
+            let name = self.current_token.clone();
+            self.advance();
+
+            if let TokenType::Assign = self.current_token.token_type {
+                self.advance();
+                return Ok(Node::NamedArg(name, Box::new(self.expression()?)));
+            }
+            self.retreat();
+        }
+
+        let arg = self.expression()?;
+        if after_named {
+            let (start, end) = arg.span();
+            return Err(Error::new(
+                ErrType::InvalidSyntax,
+                Some(start),
+                Some(end),
+                String::from("Parser: positional argument after a named argument"),
+                None,
+            ));
+        }
+        Ok(arg)
+    }
+
     fn callable(&mut self) -> Result<Node, Error> {
         let res = self.atom()?;
-        self.wrap_callable(res)
+        let res = self.wrap_callable(res)?;
+
+        match self.current_token.token_type {
+            _ if self.on_new_line() => Ok(res),
+            TokenType::PlusPlus | TokenType::MinusMinus => {
+                let op = self.current_token.clone();
+                self.advance();
+                Ok(Node::PostfixOp(res.into(), op))
+            }
+            _ => Ok(res),
+        }
         //if matches!(self.current_token.token_type, TokenType::LRound) {
         //    self.advance();
         //    let mut arg_nodes: Vec<Node> = Vec::new();
@@ -186,15 +353,22 @@ impl Parser {
         //}
     }
 
+    //unary operators bind looser than '^' so that -2^2 == -(2^2) == -4,
+    //the right side of '^' is a factor again: 2^-1 == 2^(-1)
     fn factor(&mut self) -> Result<Node, Error> {
         let t = self.current_token.clone();
 
         match t.token_type {
-            TokenType::Keywrd(Keyword::Not) | TokenType::Sub => {
+            TokenType::Keywrd(Keyword::Not) | TokenType::Sub | TokenType::Add => {
                 self.advance();
                 let factor = self.factor()?;
                 Ok(Node::UnryOp(t, factor.into()))
             }
+            TokenType::PlusPlus | TokenType::MinusMinus => {
+                self.advance();
+                let target = self.callable()?;
+                Ok(Node::PrefixOp(t, target.into()))
+            }
             _ => self.power(),
         }
     }
@@ -208,27 +382,7 @@ impl Parser {
     ) -> Result<Node, Error> {
         let mut left_node = func_a(self)?;
 
-        let res = {
-            let mut found = false;
-            for t in &ops {
-                if match_enum_type(t, &self.current_token.token_type) {
-                    found = true;
-                    break;
-                }
-            }
-            if !found {
-                if let TokenType::Keywrd(k) = &self.current_token.token_type {
-                    for key in &keywords {
-                        if match_enum_type(key, k) {
-                            found = true;
-                            break;
-                        }
-                    }
-                }
-            }
-            found
-        };
-        if res {
+        while self.current_matches(&ops, &keywords) {
             let op_token = self.current_token.clone();
             self.advance();
             let right_node = func_b(self)?;
@@ -239,6 +393,25 @@ impl Parser {
         Ok(left_node)
     }
 
+    fn current_matches(&self, ops: &[TokenType], keywords: &[Keyword]) -> bool {
+        if self.on_new_line() {
+            return false;
+        }
+        for t in ops {
+            if match_enum_type(t, &self.current_token.token_type) {
+                return true;
+            }
+        }
+        if let TokenType::Keywrd(k) = &self.current_token.token_type {
+            for key in keywords {
+                if match_enum_type(key, k) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
     fn expect_token(&self, token: TokenType) -> Result<(), Error> {
         if !match_enum_type(&self.current_token.token_type, &token) {
             Err(Error::new(
@@ -274,31 +447,8 @@ impl Parser {
         self.advance();
 
         let condition = self.expression()?;
-
-        if !match_enum_type(&self.current_token.token_type, &TokenType::LCurly) {
-            return Err(Error::new(
-                ErrType::InvalidSyntax,
-                Some(self.current_token.start_pos),
-                Some(self.current_token.end_pos),
-                format!("Parser: expected LCURLY found '{:?}'", self.current_token),
-                None,
-            ));
-        }
-
-        self.advance();
-        let expr = self.expression()?;
-        cases.push((condition, expr));
-
-        if !matches!(self.current_token.token_type, TokenType::RCurly) {
-            return Err(Error::new(
-                ErrType::InvalidSyntax,
-                Some(self.current_token.start_pos),
-                Some(self.current_token.end_pos),
-                format!("Parser: expected RCURLY found '{:?}'", self.current_token),
-                None,
-            ));
-        }
-        self.advance();
+        let body = self.block()?;
+        cases.push((condition, body));
 
         while matches!(
             self.current_token.token_type,
@@ -306,31 +456,8 @@ impl Parser {
         ) {
             self.advance();
             let cond = self.expression()?;
-
-            if !matches!(self.current_token.token_type, TokenType::LCurly) {
-                return Err(Error::new(
-                    ErrType::InvalidSyntax,
-                    Some(self.current_token.start_pos),
-                    Some(self.current_token.end_pos),
-                    format!("Parser: expected LCURLY found '{:?}'", self.current_token),
-                    None,
-                ));
-            }
-            self.advance();
-
-            let expr = self.expression()?;
-            cases.push((cond, expr));
-
-            if !matches!(self.current_token.token_type, TokenType::RCurly) {
-                return Err(Error::new(
-                    ErrType::InvalidSyntax,
-                    Some(self.current_token.start_pos),
-                    Some(self.current_token.end_pos),
-                    format!("Parser: expected RCURLY found '{:?}'", self.current_token),
-                    None,
-                ));
-            }
-            self.advance();
+            let body = self.block()?;
+            cases.push((cond, body));
         }
 
         if matches!(
@@ -338,33 +465,57 @@ impl Parser {
             TokenType::Keywrd(Keyword::Else)
         ) {
             self.advance();
-            if !matches!(self.current_token.token_type, TokenType::LCurly) {
-                return Err(Error::new(
-                    ErrType::InvalidSyntax,
-                    Some(self.current_token.start_pos),
-                    Some(self.current_token.end_pos),
-                    format!("Parser: expected LCURLY found '{:?}'", self.current_token),
-                    None,
-                ));
+            else_case = Some(Box::new(self.block()?));
+        }
+
+        Ok(Node::If(cases, else_case))
+    }
+
+    //{ statement; statement }, the block evaluates to its last statement
+    fn block(&mut self) -> Result<Node, Error> {
+        self.expect_token(TokenType::LCurly)?;
+        let start = self.current_token.start_pos;
+        self.advance();
+
+        let nodes = self.with_brackets(false, |p| p.statements(TokenType::RCurly))?;
+        let end = self.current_token.end_pos;
+        self.advance();
+
+        Ok(Node::Block(nodes, start, end))
+    }
+
+    //statements separated by ';' or newlines up to (not including) the end
+    //token, empty statements and a trailing ';' are skipped
+    fn statements(&mut self, end: TokenType) -> Result<Vec<Node>, Error> {
+        let mut nodes = Vec::new();
+
+        loop {
+            while matches!(self.current_token.token_type, TokenType::Semicln) {
+                self.advance();
             }
-            self.advance();
 
-            else_case = Some(Box::new(self.expression()?));
+            if match_enum_type(&self.current_token.token_type, &end) {
+                return Ok(nodes);
+            }
+
+            nodes.push(self.expression()?);
 
-            if !matches!(&self.current_token.token_type, &TokenType::RCurly) {
+            if !matches!(self.current_token.token_type, TokenType::Semicln)
+                && !match_enum_type(&self.current_token.token_type, &end)
+                && !self.on_new_line()
+            {
                 return Err(Error::new(
                     ErrType::InvalidSyntax,
                     Some(self.current_token.start_pos),
                     Some(self.current_token.end_pos),
-                    format!("Parser: expected RCURLY found '{:?}'", self.current_token),
+                    format!(
+                        "Parser: expected Semicln or {:?} found {:?}",
+                        end, self.current_token.token_type
+                    ),
                     None,
                 ));
             }
-
-            self.advance();
         }
-
-        Ok(Node::If(cases, else_case))
     }
 
     fn array_expression(&mut self) -> Result<Node, Error> {
@@ -372,16 +523,22 @@ impl Parser {
         let start = self.current_token.start_pos;
         self.advance();
 
-        let mut array: Vec<Node> = Vec::new();
+        let array = self.with_brackets(true, |p| {
+            let mut array: Vec<Node> = Vec::new();
 
-        if !matches!(self.current_token.token_type, TokenType::RBrace) {
-            array.push(self.expression()?);
-        }
+            if !matches!(p.current_token.token_type, TokenType::RBrace) {
+                array.push(p.expression()?);
+            }
 
-        while matches!(self.current_token.token_type, TokenType::Comma) {
-            self.advance();
-            array.push(self.expression()?);
-        }
+            while matches!(p.current_token.token_type, TokenType::Comma) {
+                p.advance();
+                if matches!(p.current_token.token_type, TokenType::RBrace) {
+                    break;
+                }
+                array.push(p.expression()?);
+            }
+            Ok(array)
+        })?;
 
         self.expect_token(TokenType::RBrace)?;
         let end = self.current_token.end_pos;
@@ -390,6 +547,61 @@ impl Parser {
         Ok(Node::Array(array, start, end))
     }
 
+    //{key: value, ...}, keys are expressions that evaluate to strings
+    fn map_expression(&mut self) -> Result<Node, Error> {
+        self.expect_token(TokenType::LCurly)?;
+        let start = self.current_token.start_pos;
+        self.advance();
+
+        let entries = self.with_brackets(true, |p| {
+            let mut entries: Vec<(Node, Node)> = Vec::new();
+
+            while !matches!(p.current_token.token_type, TokenType::RCurly) {
+                if !entries.is_empty() {
+                    p.expect_token(TokenType::Comma)?;
+                    p.advance();
+                    if matches!(p.current_token.token_type, TokenType::RCurly) {
+                        break;
+                    }
+                }
+
+                let key = p.expression()?;
+                p.expect_token(TokenType::Colon)?;
+                p.advance();
+                entries.push((key, p.expression()?));
+            }
+            Ok(entries)
+        })?;
+
+        let end = self.current_token.end_pos;
+        self.advance();
+
+        Ok(Node::Map(entries, start, end))
+    }
+
+    //'name' or 'name = default', only trailing parameters can have defaults
+    fn parameter(&mut self, after_default: bool) -> Result<(Token, Option<Node>), Error> {
+        self.expect_token(TokenType::Id(String::from("")))?;
+        let name = self.current_token.clone();
+        self.advance();
+
+        if !matches!(self.current_token.token_type, TokenType::Assign) {
+            if after_default {
+                return Err(Error::new(
+                    ErrType::InvalidSyntax,
+                    Some(name.start_pos),
+                    Some(name.end_pos),
+                    format!("Parser: parameter {:?} needs a default value", name),
+                    None,
+                ));
+            }
+            return Ok((name, None));
+        }
+        self.advance();
+
+        Ok((name, Some(self.expression()?)))
+    }
+
     fn func_expression(&mut self) -> Result<Node, Error> {
         let mut start: Option<Position> = None;
         let end: Option<Position>;
@@ -420,24 +632,27 @@ impl Parser {
         self.expect_token(TokenType::LRound)?;
         self.advance();
 
-        let mut arg_tokens: Vec<Token> = Vec::new();
-
-        if matches!(self.current_token.token_type, TokenType::Id(_),) {
-            arg_tokens.push(self.current_token.clone());
-            if start.is_none() {
-                start = Some(self.current_token.start_pos);
-            }
+        if start.is_none() && matches!(self.current_token.token_type, TokenType::Id(_)) {
+            start = Some(self.current_token.start_pos);
+        }
 
-            self.advance();
+        let arg_tokens = self.with_brackets(true, |p| {
+            let mut arg_tokens: Vec<(Token, Option<Node>)> = Vec::new();
 
-            while matches!(self.current_token.token_type, TokenType::Comma) {
-                self.advance();
-                self.expect_token(TokenType::Id(String::from("")))?;
+            if matches!(p.current_token.token_type, TokenType::Id(_),) {
+                arg_tokens.push(p.parameter(false)?);
 
-                arg_tokens.push(self.current_token.clone());
-                self.advance();
+                while matches!(p.current_token.token_type, TokenType::Comma) {
+                    p.advance();
+                    if matches!(p.current_token.token_type, TokenType::RRound) {
+                        break;
+                    }
+                    let after_default = arg_tokens.last().is_some_and(|(_, d)| d.is_some());
+                    arg_tokens.push(p.parameter(after_default)?);
+                }
             }
-        }
+            Ok(arg_tokens)
+        })?;
         self.expect_token(TokenType::RRound)?;
 
         self.advance();
@@ -448,15 +663,9 @@ impl Parser {
             start = Some(self.current_token.start_pos);
         }
 
-        self.advance();
-
-        let body = self.expression()?;
-
-        self.expect_token(TokenType::RCurly)?;
-
-        end = Some(self.current_token.end_pos);
-
-        self.advance();
+        let body = self.with_loop(false, Parser::block)?;
+        end = Some(body.span().1);
+        check_const_parameters(&arg_tokens, &body)?;
 
         Ok(Node::FuncDef(
             var_name,
@@ -469,6 +678,7 @@ impl Parser {
 
     fn for_expression(&mut self) -> Result<Node, Error> {
         let mut c1: Option<Box<Node>> = None;
+        let mut c2: Option<Box<Node>> = None;
         let mut c3: Option<Box<Node>> = None;
 
         let start: Position;
@@ -490,6 +700,18 @@ impl Parser {
         self.advance();
 
         start = self.current_token.start_pos;
+
+        if matches!(self.current_token.token_type, TokenType::Id(_)) {
+            let var = self.current_token.clone();
+            self.advance();
+
+            if matches!(self.current_token.token_type, TokenType::Keywrd(Keyword::In)) {
+                self.advance();
+                return self.foreach_expression(var, start);
+            }
+            self.retreat();
+        }
+
         if !match_enum_type(&self.current_token.token_type, &TokenType::Semicln) {
             c1 = Some(self.expression()?.into());
         }
@@ -497,7 +719,10 @@ impl Parser {
         self.expect_token(TokenType::Semicln)?;
         self.advance();
 
-        let c2 = self.expression()?;
+        //no condition loops forever: for ;; { ... }
+        if !match_enum_type(&self.current_token.token_type, &TokenType::Semicln) {
+            c2 = Some(self.expression()?.into());
+        }
 
         self.expect_token(TokenType::Semicln)?;
         self.advance();
@@ -506,16 +731,77 @@ impl Parser {
             c3 = Some(self.expression()?.into());
         }
 
-        self.expect_token(TokenType::LCurly)?;
+        let body = self.with_loop(true, Parser::block)?;
+        let else_case = self.loop_else()?;
+        end = else_case.as_ref().unwrap_or(&body).span().1;
+
+        Ok(Node::For(c1, c2, c3, body.into(), else_case.map(Box::new), start, end))
+    }
+
+    fn foreach_expression(&mut self, var: Token, start: Position) -> Result<Node, Error> {
+        let collection = self.expression()?;
+        let body = self.with_loop(true, Parser::block)?;
+        let else_case = self.loop_else()?;
+        let end = else_case.as_ref().unwrap_or(&body).span().1;
+
+        Ok(Node::ForEach(var, collection.into(), body.into(), else_case.map(Box::new), start, end))
+    }
+
+    //'else { ... }' after a loop body, a 'break' in it belongs to the enclosing loop
+    fn loop_else(&mut self) -> Result<Option<Node>, Error> {
+        if !matches!(self.current_token.token_type, TokenType::Keywrd(Keyword::Else)) {
+            return Ok(None);
+        }
         self.advance();
 
-        let body = self.expression()?;
+        Ok(Some(self.block()?))
+    }
+
+    fn del_expression(&mut self) -> Result<Node, Error> {
+        self.advance();
+
+        self.expect_token(TokenType::Id(String::new()))?;
+        let var = self.current_token.clone();
+        self.advance();
+
+        Ok(Node::Del(var))
+    }
+
+    fn import_expression(&mut self) -> Result<Node, Error> {
+        self.advance();
+
+        self.expect_token(TokenType::String(String::new()))?;
+        let path = self.current_token.clone();
+        self.advance();
+
+        Ok(Node::Import(path))
+    }
 
-        self.expect_token(TokenType::RCurly)?;
-        end = self.current_token.start_pos;
+    fn throw_expression(&mut self) -> Result<Node, Error> {
+        let start = self.current_token.start_pos;
+        self.advance();
+
+        let value = self.expression()?;
+        let end = value.span().1;
+
+        Ok(Node::Throw(value.into(), start, end))
+    }
+
+    fn break_expression(&mut self) -> Result<Node, Error> {
+        let token = self.current_token.clone();
+
+        if !self.in_loop {
+            return Err(Error::new(
+                ErrType::InvalidSyntax,
+                Some(token.start_pos),
+                Some(token.end_pos),
+                String::from("Parser: 'break' outside of a loop"),
+                None,
+            ));
+        }
         self.advance();
 
-        Ok(Node::For(c1, c2.into(), c3, body.into(), start, end))
+        Ok(Node::Break(token))
     }
 
     fn while_expression(&mut self) -> Result<Node, Error> {
@@ -536,19 +822,62 @@ impl Parser {
 
         self.advance();
         let cond = self.expression()?;
+        let body = self.with_loop(true, Parser::block)?;
+        let else_case = self.loop_else()?;
+        let end = else_case.as_ref().unwrap_or(&body).span().1;
 
-        self.expect_token(TokenType::LCurly)?;
+        Ok(Node::While(cond.into(), body.into(), else_case.map(Box::new), start, end))
+    }
+
+    fn do_while_expression(&mut self) -> Result<Node, Error> {
+        let start = self.current_token.start_pos;
+
+        self.advance();
+        let body = self.with_loop(true, Parser::block)?;
+
+        //expect_token only compares the variant, not the keyword
+        if !matches!(self.current_token.token_type, TokenType::Keywrd(Keyword::While)) {
+            return Err(Error::new(
+                ErrType::InvalidSyntax,
+                Some(self.current_token.start_pos),
+                Some(self.current_token.end_pos),
+                format!("Parser: expected WHILE found '{:?}'", self.current_token),
+                None,
+            ));
+        }
         self.advance();
+        let cond = self.expression()?;
+        let else_case = self.loop_else()?;
+        let end = else_case.as_ref().unwrap_or(&cond).span().1;
+
+        Ok(Node::DoWhile(body.into(), cond.into(), else_case.map(Box::new), start, end))
+    }
 
-        let body = self.expression()?;
+    fn try_expression(&mut self) -> Result<Node, Error> {
+        let start = self.current_token.start_pos;
 
-        self.expect_token(TokenType::RCurly)?;
+        self.advance();
+        let body = self.block()?;
 
-        let end = self.current_token.end_pos;
+        if !matches!(self.current_token.token_type, TokenType::Keywrd(Keyword::Catch)) {
+            return Err(Error::new(
+                ErrType::InvalidSyntax,
+                Some(self.current_token.start_pos),
+                Some(self.current_token.end_pos),
+                format!("Parser: expected CATCH found '{:?}'", self.current_token),
+                None,
+            ));
+        }
+        self.advance();
 
+        self.expect_token(TokenType::Id(String::new()))?;
+        let id = self.current_token.clone();
         self.advance();
 
-        Ok(Node::While(cond.into(), body.into(), start, end))
+        let handler = self.block()?;
+        let end = handler.span().1;
+
+        Ok(Node::Try(body.into(), id, handler.into(), start, end))
     }
 
     fn arith_expression(&mut self) -> Result<Node, Error> {
@@ -560,74 +889,222 @@ impl Parser {
         )
     }
 
+    //comparisons are non-associative, 'a < b < c' is rejected instead of
+    //silently comparing the bool of 'a < b' with c
     fn comp_expression(&mut self) -> Result<Node, Error> {
-        match self.current_token.token_type {
-            //TokenType::Keywrd(Keyword::Not) => {
-            //    let op = self.current_token.clone();
-            //    self.advance();
-            //    let node = self.comp_expression()?;
-            //    Ok(Node::UnryOp(op, Box::new(node)))
-            //}
-
-            _ => self.binary_operation(
-                Parser::arith_expression,
-                vec![
-                    TokenType::Equal,
-                    TokenType::AddEq,
-                    TokenType::SubEq,
-                    TokenType::NEqual,
-                    TokenType::Less,
-                    TokenType::LessEq,
-                    TokenType::Greater,
-                    TokenType::GreaterEq,
-                ],
-                Vec::new(),
-                Parser::arith_expression)
-            //{
-            //    Ok(node) => Ok(node),
-            //    Err(e) => Err(e),
-            //    //TODO: look at error handling again
-            //},
+        let ops = [
+            TokenType::Equal,
+            TokenType::NEqual,
+            TokenType::Less,
+            TokenType::LessEq,
+            TokenType::Greater,
+            TokenType::GreaterEq,
+        ];
+
+        let left = self.arith_expression()?;
+
+        if !self.current_matches(&ops, &[]) {
+            return Ok(left);
         }
+
+        let op_token = self.current_token.clone();
+        self.advance();
+        let right = self.arith_expression()?;
+
+        if self.current_matches(&ops, &[]) {
+            return Err(Error::new(
+                ErrType::InvalidSyntax,
+                Some(self.current_token.start_pos),
+                Some(self.current_token.end_pos),
+                format!(
+                    "Parser: comparison operators can not be chained, found {:?} after {:?}, use '&&' to combine comparisons",
+                    self.current_token.token_type, op_token.token_type
+                ),
+                None,
+            ));
+        }
+
+        Ok(Node::BinOp(left.into(), op_token, right.into()))
     }
 
     fn term(&mut self) -> Result<Node, Error> {
         self.binary_operation(
             Parser::factor,
-            vec![TokenType::Mul, TokenType::Div],
+            vec![TokenType::Mul, TokenType::Div, TokenType::Mod],
             Vec::new(),
             Parser::factor,
         )
     }
 
     fn expression(&mut self) -> Result<Node, Error> {
-        match self.current_token.token_type {
-            TokenType::Id(_) => {
-                let var = self.current_token.clone();
+        if let TokenType::Keywrd(Keyword::Let | Keyword::Const) = self.current_token.token_type {
+            let is_const = matches!(self.current_token.token_type, TokenType::Keywrd(Keyword::Const));
+            self.advance();
+
+            self.expect_token(TokenType::Id(String::new()))?;
+            let var = self.current_token.clone();
+            self.advance();
+
+            self.expect_token(TokenType::Assign)?;
+            self.advance();
+
+            let value = Box::new(self.expression()?);
+            return Ok(if is_const {
+                Node::Const(var, value)
+            } else {
+                Node::Let(var, value)
+            });
+        }
+
+        if let TokenType::Id(_) = self.current_token.token_type {
+            let var = self.current_token.clone();
+            self.advance();
+
+            if let TokenType::Assign = self.current_token.token_type {
                 self.advance();
+                return Ok(Node::Assign(var, Box::new(self.expression()?)));
+            }
+            self.retreat();
 
-                match self.current_token.token_type {
-                    TokenType::Assign => {
-                        self.advance();
-                        Ok(Node::Assign(var, Box::new(self.expression()?)))
-                    }
-                    _ => {
-                        self.retreat();
-                        self.binary_operation(
-                            Parser::comp_expression,
-                            Vec::new(),
-                            vec![Keyword::And, Keyword::Or],
-                            Parser::comp_expression,
-                        )
-                    }
-                }
+            if let Some(node) = self.multi_assignment()? {
+                return Ok(node);
+            }
+        }
+
+        let node = self.ternary_expression()?;
+        self.compound_assignment(node)
+    }
+
+    //'a, b = [1, 2]' or 'a, b = 1, 2'. The whole right side is evaluated
+    //before anything is assigned, so 'a, b = b, a' swaps. Inside brackets
+    //the commas separate arguments and elements instead
+    fn multi_assignment(&mut self) -> Result<Option<Node>, Error> {
+        if self.in_brackets {
+            return Ok(None);
+        }
+
+        let start_index = self.token_index;
+        let mut ids = vec![self.current_token.clone()];
+        self.advance();
+
+        while matches!(self.current_token.token_type, TokenType::Comma) {
+            self.advance();
+            match self.current_token.token_type {
+                TokenType::Id(_) => ids.push(self.current_token.clone()),
+                _ => break,
+            }
+            self.advance();
+        }
+
+        if ids.len() < 2 || !matches!(self.current_token.token_type, TokenType::Assign) {
+            while self.token_index > start_index {
+                self.retreat();
+            }
+            return Ok(None);
+        }
+        self.advance();
+
+        let mut values = vec![self.expression()?];
+        while matches!(self.current_token.token_type, TokenType::Comma) {
+            self.advance();
+            values.push(self.expression()?);
+        }
+
+        let value = if values.len() == 1 {
+            values.pop().unwrap()
+        } else {
+            let start = values.first().unwrap().span().0;
+            let end = values.last().unwrap().span().1;
+            Node::Array(values, start, end)
+        };
+
+        Ok(Some(Node::MultiAssign(ids, Box::new(value))))
+    }
+
+    //'a ? b : c ? d : e' groups as 'a ? b : (c ? d : e)'
+    fn ternary_expression(&mut self) -> Result<Node, Error> {
+        let condition = self.coalesce_expression()?;
+
+        if self.on_new_line() || !matches!(self.current_token.token_type, TokenType::Question) {
+            return Ok(condition);
+        }
+        self.advance();
+
+        let then_expr = self.expression()?;
+
+        self.expect_token(TokenType::Colon)?;
+        self.advance();
+
+        let else_expr = self.ternary_expression()?;
+
+        Ok(Node::Ternary(
+            condition.into(),
+            then_expr.into(),
+            else_expr.into(),
+        ))
+    }
+
+    //'a ?? b' binds looser than && and ||
+    fn coalesce_expression(&mut self) -> Result<Node, Error> {
+        self.binary_operation(
+            Parser::logic_expression,
+            vec![TokenType::Coalesce],
+            Vec::new(),
+            Parser::logic_expression,
+        )
+    }
+
+    fn logic_expression(&mut self) -> Result<Node, Error> {
+        self.binary_operation(
+            Parser::comp_expression,
+            Vec::new(),
+            vec![Keyword::And, Keyword::Or],
+            Parser::comp_expression,
+        )
+    }
+
+    //compound assignments bind loosest and associate right like '=',
+    //the target is checked for being an LVALUE at runtime
+    fn compound_assignment(&mut self, node: Node) -> Result<Node, Error> {
+        let ops = [
+            TokenType::AddEq,
+            TokenType::SubEq,
+            TokenType::MulEq,
+            TokenType::DivEq,
+            TokenType::ModEq,
+        ];
+
+        if self.current_matches(&ops, &[]) {
+            let op_token = self.current_token.clone();
+            self.advance();
+            let value = self.expression()?;
+            return Ok(Node::BinOp(node.into(), op_token, value.into()));
+        }
+        Ok(node)
+    }
+}
+
+//parameters are bound mutably, so the function body can't redeclare one as const
+fn check_const_parameters(params: &[(Token, Option<Node>)], body: &Node) -> Result<(), Error> {
+    let Node::Block(nodes, ..) = body else {
+        return Ok(());
+    };
+
+    for node in nodes {
+        if let Node::Const(id @ Token { token_type: TokenType::Id(name), .. }, _) = node {
+            if params
+                .iter()
+                .any(|(param, _)| matches!(&param.token_type, TokenType::Id(p) if p == name))
+            {
+                return Err(Error::new(
+                    ErrType::InvalidSyntax,
+                    Some(id.start_pos),
+                    Some(id.end_pos),
+                    format!("Parser: const {:?} shadows a parameter of the function", name),
+                    None,
+                ));
             }
-            _ => self.binary_operation(
-                Parser::comp_expression,
-                Vec::new(),
-                vec![Keyword::And, Keyword::Or],
-                Parser::comp_expression,
-            ),
         }
     }
+    Ok(())
 }